@@ -0,0 +1,406 @@
+use clap::Subcommand;
+use std::{
+    path::{Path, PathBuf},
+    process::Stdio,
+    time::Duration,
+};
+
+use crate::{
+    client::{self, Client, Endpoint},
+    config::Config,
+    daemon, runtime, Args, DEFAULT_PORT,
+};
+
+/// How long `open`/`add` wait for a freshly spawned server to answer.
+const SPAWN_WAIT: Duration = Duration::from_secs(5);
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Start the server (the default when no subcommand is given)
+    Serve,
+    /// Open a markdown file in the browser, starting a server if needed
+    Open {
+        /// Markdown file to open
+        file: PathBuf,
+        /// Only navigate already open preview tabs instead of launching a browser
+        #[arg(long)]
+        no_browser: bool,
+    },
+    /// Register a directory as a workspace, starting a server if needed
+    Add {
+        /// Directory to register [default: current directory]
+        dir: Option<PathBuf>,
+        /// Render raw HTML in this workspace without sanitizing
+        #[arg(long)]
+        trusted: bool,
+        /// Display name [default: directory name]
+        #[arg(long)]
+        name: Option<String>,
+        /// Short name usable in place of the workspace id in URLs
+        #[arg(long)]
+        alias: Option<String>,
+    },
+    /// Remove a registered workspace
+    Remove {
+        /// Workspace id or alias as shown by `mdv status`
+        id: String,
+    },
+    /// Show server status and registered workspaces
+    Status,
+    /// Stop the running server
+    Stop,
+    /// Inspect the configuration
+    Config {
+        #[command(subcommand)]
+        action: ConfigCommand,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ConfigCommand {
+    /// Print the effective configuration as TOML
+    Show {
+        /// Also apply this workspace's .mdv.toml
+        #[arg(long, value_name = "DIR")]
+        workspace: Option<PathBuf>,
+    },
+}
+
+/// Runs a client subcommand against the server described by `args`.
+pub async fn run(command: Command, args: &Args, config: &Config) -> Result<(), String> {
+    if let Command::Config { action } = command {
+        return run_config(action, config);
+    }
+    let client = client_for(args).map_err(|e| format!("Cannot configure client: {}", e))?;
+
+    match command {
+        Command::Serve => unreachable!("serve is handled by main"),
+        Command::Open { file, no_browser } => open(&client, args, &file, no_browser).await,
+        Command::Add { dir, trusted, name, alias } => {
+            let options = RegisterOptions { trusted: trusted.then_some(true), name, alias };
+            add(&client, args, dir, &options).await
+        }
+        Command::Remove { id } => remove(&client, &id).await,
+        Command::Status => status(&client, args).await,
+        Command::Stop => stop(&client, args).await,
+        Command::Config { .. } => unreachable!("handled above"),
+    }
+}
+
+fn run_config(action: ConfigCommand, config: &Config) -> Result<(), String> {
+    match action {
+        ConfigCommand::Show { workspace } => {
+            let config = match workspace {
+                Some(dir) => config.for_workspace(&dir)?,
+                None => config.clone(),
+            };
+            print!("{}", config.to_toml());
+            Ok(())
+        }
+    }
+}
+
+fn client_for(args: &Args) -> std::io::Result<Client> {
+    let endpoint = match &args.socket {
+        #[cfg(unix)]
+        Some(path) => Endpoint::Unix(path.clone()),
+        _ => Endpoint::Tcp {
+            host: args.host().to_string(),
+            port: args.port.unwrap_or(DEFAULT_PORT),
+        },
+    };
+    Client::new(endpoint, crate::tls_root(args).as_deref())
+}
+
+/// Base URL for browsers, or `None` when the server has no TCP listener.
+pub fn browser_base_url(args: &Args) -> Option<String> {
+    if args.socket.is_some() && args.port.is_none() {
+        return None;
+    }
+    let scheme = if crate::tls_root(args).is_some() { "https" } else { "http" };
+    Some(format!(
+        "{}://{}:{}",
+        scheme,
+        client::host_for_url(args.host()),
+        args.port.unwrap_or(DEFAULT_PORT)
+    ))
+}
+
+async fn is_running(client: &Client) -> bool {
+    matches!(client.get("/api/status").await, Ok(resp) if resp.status.is_success())
+}
+
+/// Starts a detached `mdv serve` with the same server options unless one
+/// is already answering, then waits for it to come up.
+async fn ensure_server(client: &Client, args: &Args) -> Result<(), String> {
+    if is_running(client).await {
+        return Ok(());
+    }
+
+    let mut serve_args = vec!["serve".into()];
+    serve_args.extend(args.to_cli_args());
+    let log = runtime::log_path(&runtime::runtime_dir(), &args.instance_name());
+    daemon::spawn_detached(&serve_args, &log)
+        .map_err(|e| format!("Cannot start mdv server: {}", e))?;
+
+    let deadline = tokio::time::Instant::now() + SPAWN_WAIT;
+    while tokio::time::Instant::now() < deadline {
+        tokio::time::sleep(POLL_INTERVAL).await;
+        if is_running(client).await {
+            return Ok(());
+        }
+    }
+    Err("Timed out waiting for mdv server to start".to_string())
+}
+
+/// Finds the project root for a file: the nearest ancestor holding a
+/// `.git` or `.hg` directory, falling back to the file's own directory.
+pub fn find_project_root(file: &Path) -> PathBuf {
+    let start = file.parent().unwrap_or(file);
+    start
+        .ancestors()
+        .find(|dir| dir.join(".git").exists() || dir.join(".hg").is_dir())
+        .unwrap_or(start)
+        .to_path_buf()
+}
+
+/// Optional settings sent with a workspace registration.
+#[derive(Default)]
+struct RegisterOptions {
+    trusted: Option<bool>,
+    name: Option<String>,
+    alias: Option<String>,
+}
+
+async fn register(client: &Client, dir: &Path, options: &RegisterOptions) -> Result<serde_json::Value, String> {
+    let mut body = serde_json::json!({ "path": dir.to_string_lossy() });
+    if let Some(trusted) = options.trusted {
+        body["trusted"] = serde_json::Value::Bool(trusted);
+    }
+    if let Some(name) = &options.name {
+        body["name"] = serde_json::Value::from(name.as_str());
+    }
+    if let Some(alias) = &options.alias {
+        body["alias"] = serde_json::Value::from(alias.as_str());
+    }
+    let resp = client
+        .post("/api/workspace/register", &body)
+        .await
+        .map_err(|e| format!("Cannot reach mdv server: {}", e))?;
+    if !resp.status.is_success() {
+        return Err(resp.error_message());
+    }
+    Ok(resp.body)
+}
+
+async fn open(client: &Client, args: &Args, file: &Path, no_browser: bool) -> Result<(), String> {
+    let file = file
+        .canonicalize()
+        .map_err(|e| format!("Cannot open {}: {}", file.display(), e))?;
+    if !file.is_file() {
+        return Err(format!("{} is not a file", file.display()));
+    }
+
+    ensure_server(client, args).await?;
+    register(client, &find_project_root(&file), &RegisterOptions::default()).await?;
+
+    let query = serde_urlencoded::to_string([("path", file.to_string_lossy())])
+        .map_err(|e| e.to_string())?;
+    let resp = client
+        .get(&format!("/api/active?{}", query))
+        .await
+        .map_err(|e| format!("Cannot reach mdv server: {}", e))?;
+    if !resp.status.is_success() {
+        return Err(resp.error_message());
+    }
+
+    let path = resp.body["url"].as_str().unwrap_or_default();
+    match browser_base_url(args) {
+        Some(base) => {
+            let url = format!("{}{}", base, path);
+            println!("{}", url);
+            if !no_browser {
+                open_browser(&url);
+            }
+        }
+        None => println!("{} (server has no TCP listener)", path),
+    }
+    Ok(())
+}
+
+async fn add(client: &Client, args: &Args, dir: Option<PathBuf>, options: &RegisterOptions) -> Result<(), String> {
+    let dir = match dir {
+        Some(dir) => dir,
+        None => std::env::current_dir().map_err(|e| e.to_string())?,
+    };
+
+    ensure_server(client, args).await?;
+    let body = register(client, &dir, options).await?;
+    println!(
+        "Workspace added: {} ({})",
+        body["name"].as_str().unwrap_or_default(),
+        body["alias"].as_str().or(body["id"].as_str()).unwrap_or_default()
+    );
+    Ok(())
+}
+
+async fn remove(client: &Client, id: &str) -> Result<(), String> {
+    let resp = client
+        .delete(&format!("/api/workspace/{}", id))
+        .await
+        .map_err(|_| "mdv server is not running".to_string())?;
+    if !resp.status.is_success() {
+        return Err(resp.error_message());
+    }
+    println!("Workspace removed: {}", id);
+    Ok(())
+}
+
+async fn status(client: &Client, args: &Args) -> Result<(), String> {
+    let resp = client
+        .get("/api/status")
+        .await
+        .map_err(|_| "mdv server is not running".to_string())?;
+    if !resp.status.is_success() {
+        return Err(resp.error_message());
+    }
+
+    match browser_base_url(args) {
+        Some(base) => println!("mdv server running at {} (pid {})", base, resp.body["pid"]),
+        None => println!("mdv server running (pid {})", resp.body["pid"]),
+    }
+    if let Some(socket) = &args.socket {
+        println!("API socket: {}", socket.display());
+    }
+
+    let workspaces = resp.body["workspaces"].as_array().cloned().unwrap_or_default();
+    if workspaces.is_empty() {
+        println!("No workspaces registered");
+    } else {
+        println!("Workspaces:");
+        for ws in workspaces {
+            let id = match ws["alias"].as_str() {
+                Some(alias) => format!("{}, alias {}", ws["id"].as_str().unwrap_or_default(), alias),
+                None => ws["id"].as_str().unwrap_or_default().to_string(),
+            };
+            println!(
+                "  - {} ({}) {}",
+                ws["name"].as_str().unwrap_or_default(),
+                id,
+                ws["path"].as_str().unwrap_or_default()
+            );
+        }
+    }
+    Ok(())
+}
+
+async fn stop(client: &Client, args: &Args) -> Result<(), String> {
+    if !is_running(client).await {
+        return Err("mdv server is not running".to_string());
+    }
+    let token = runtime::read_info(&runtime::runtime_dir(), &args.instance_name())
+        .map(|info| info.token)
+        .ok_or("Cannot find the server runtime file (was it started by another user?)")?;
+
+    let client = client_for(args)
+        .map_err(|e| format!("Cannot configure client: {}", e))?
+        .with_token(Some(token));
+    let resp = client
+        .post("/api/shutdown", &serde_json::json!({}))
+        .await
+        .map_err(|e| format!("Cannot reach mdv server: {}", e))?;
+    if !resp.status.is_success() {
+        return Err(resp.error_message());
+    }
+
+    let deadline = tokio::time::Instant::now() + SPAWN_WAIT;
+    while tokio::time::Instant::now() < deadline {
+        if !is_running(&client).await {
+            println!("mdv server stopped");
+            return Ok(());
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+    Err("mdv server did not stop".to_string())
+}
+
+/// Percent-encodes the bytes of `url` that may not appear in a URL
+/// (spaces, quotes, `^`, `|`, non-ASCII, ...). Existing escapes are kept.
+fn escape_url(url: &str) -> String {
+    let mut escaped = String::with_capacity(url.len());
+    for byte in url.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~:/?#[]@!$&'()*+,;=%".contains(&byte) {
+            escaped.push(byte as char);
+        } else {
+            escaped.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    escaped
+}
+
+/// Opens `url` with the platform's default browser, ignoring failures.
+/// No shell sees the URL, so `&` and friends in file names stay inert.
+pub fn open_browser(url: &str) {
+    let mut command = if cfg!(target_os = "macos") {
+        std::process::Command::new("open")
+    } else if cfg!(windows) {
+        let mut c = std::process::Command::new("rundll32");
+        c.arg("url.dll,FileProtocolHandler");
+        c
+    } else if which("wslview") {
+        std::process::Command::new("wslview")
+    } else {
+        std::process::Command::new("xdg-open")
+    };
+    let _ = command
+        .arg(escape_url(url))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+}
+
+fn which(program: &str) -> bool {
+    std::env::var_os("PATH")
+        .map(|paths| std::env::split_paths(&paths).any(|dir| dir.join(program).is_file()))
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{self, File};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_escape_url() {
+        assert_eq!(escape_url("http://127.0.0.1:3000/view/ws/a.md#x"), "http://127.0.0.1:3000/view/ws/a.md#x");
+        assert_eq!(escape_url("http://h/view/ws/my%20docs/a^b|c.md"), "http://h/view/ws/my%20docs/a%5Eb%7Cc.md");
+        assert_eq!(escape_url("http://h/\"x\" 日.md"), "http://h/%22x%22%20%E6%97%A5.md");
+    }
+
+    #[test]
+    fn test_find_project_root_git() {
+        let temp = TempDir::new().unwrap();
+        fs::create_dir(temp.path().join(".git")).unwrap();
+        let docs = temp.path().join("docs/api");
+        fs::create_dir_all(&docs).unwrap();
+        File::create(docs.join("readme.md")).unwrap();
+
+        assert_eq!(find_project_root(&docs.join("readme.md")), temp.path());
+    }
+
+    #[test]
+    fn test_find_project_root_fallback_to_parent() {
+        let temp = TempDir::new().unwrap();
+        let docs = temp.path().join("docs");
+        fs::create_dir(&docs).unwrap();
+        File::create(docs.join("readme.md")).unwrap();
+
+        // No VCS marker anywhere below the temp dir, but an ancestor of the
+        // temp dir might have one; only check we never go below the file.
+        let root = find_project_root(&docs.join("readme.md"));
+        assert!(docs.starts_with(&root));
+    }
+}
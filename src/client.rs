@@ -0,0 +1,230 @@
+use axum::http::{header, Method, Request, StatusCode};
+use http_body_util::{BodyExt, Full};
+use hyper::body::Bytes;
+use hyper_util::rt::TokioIo;
+use rustls_pki_types::{pem::PemObject, CertificateDer, ServerName};
+use std::{io, path::Path, path::PathBuf, sync::Arc, time::Duration};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_rustls::{rustls, TlsConnector};
+
+/// Upper bound for a single API call to a running server.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Where a running mdv server can be reached.
+pub enum Endpoint {
+    Tcp { host: String, port: u16 },
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+/// Response of an API call: status plus decoded JSON body
+/// (`Value::Null` when the body is not JSON).
+pub struct ApiResponse {
+    pub status: StatusCode,
+    pub body: serde_json::Value,
+}
+
+impl ApiResponse {
+    /// Returns the `error` message of a failed call, or a generic description.
+    pub fn error_message(&self) -> String {
+        self.body
+            .get("error")
+            .and_then(|e| e.as_str())
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("server returned {}", self.status))
+    }
+}
+
+/// Minimal HTTP/1.1 client for the mdv JSON API.
+pub struct Client {
+    endpoint: Endpoint,
+    tls: Option<TlsConnector>,
+    token: Option<String>,
+}
+
+impl Client {
+    /// Creates a client. When `tls_root` is given, TCP connections use
+    /// HTTPS and trust that PEM certificate (typically the server's own
+    /// self-signed certificate).
+    pub fn new(endpoint: Endpoint, tls_root: Option<&Path>) -> io::Result<Self> {
+        let tls = match (&endpoint, tls_root) {
+            (Endpoint::Tcp { .. }, Some(root)) => Some(tls_connector(root)?),
+            _ => None,
+        };
+        Ok(Self {
+            endpoint,
+            tls,
+            token: None,
+        })
+    }
+
+    /// Sends `token` as a bearer token with every request.
+    pub fn with_token(mut self, token: Option<String>) -> Self {
+        self.token = token;
+        self
+    }
+
+    pub async fn get(&self, path: &str) -> io::Result<ApiResponse> {
+        self.request(Method::GET, path, None).await
+    }
+
+    pub async fn post(&self, path: &str, body: &serde_json::Value) -> io::Result<ApiResponse> {
+        self.request(Method::POST, path, Some(body)).await
+    }
+
+    pub async fn delete(&self, path: &str) -> io::Result<ApiResponse> {
+        self.request(Method::DELETE, path, None).await
+    }
+
+    async fn request(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&serde_json::Value>,
+    ) -> io::Result<ApiResponse> {
+        let host = match &self.endpoint {
+            Endpoint::Tcp { host, port } => format!("{}:{}", host_for_url(host), port),
+            #[cfg(unix)]
+            Endpoint::Unix(_) => "localhost".to_string(),
+        };
+
+        let mut builder = Request::builder()
+            .method(method)
+            .uri(path)
+            .header(header::HOST, host);
+        if let Some(token) = &self.token {
+            builder = builder.header(header::AUTHORIZATION, format!("Bearer {}", token));
+        }
+        let body = match body {
+            Some(json) => {
+                builder = builder.header(header::CONTENT_TYPE, "application/json");
+                Full::new(Bytes::from(json.to_string()))
+            }
+            None => Full::new(Bytes::new()),
+        };
+        let request = builder.body(body).map_err(io::Error::other)?;
+
+        tokio::time::timeout(REQUEST_TIMEOUT, self.send(request))
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "request timed out"))?
+    }
+
+    async fn send(&self, request: Request<Full<Bytes>>) -> io::Result<ApiResponse> {
+        match &self.endpoint {
+            Endpoint::Tcp { host, port } => {
+                let stream = tokio::net::TcpStream::connect((connect_host(host), *port)).await?;
+                match &self.tls {
+                    Some(connector) => {
+                        let name = ServerName::try_from(connect_host(host).to_string())
+                            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+                        let stream = connector.connect(name, stream).await?;
+                        send_over(stream, request).await
+                    }
+                    None => send_over(stream, request).await,
+                }
+            }
+            #[cfg(unix)]
+            Endpoint::Unix(path) => {
+                let stream = tokio::net::UnixStream::connect(path).await?;
+                send_over(stream, request).await
+            }
+        }
+    }
+}
+
+async fn send_over<S>(stream: S, request: Request<Full<Bytes>>) -> io::Result<ApiResponse>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (mut sender, conn) = hyper::client::conn::http1::handshake(TokioIo::new(stream))
+        .await
+        .map_err(io::Error::other)?;
+    tokio::spawn(conn);
+
+    let response = sender.send_request(request).await.map_err(io::Error::other)?;
+    let status = response.status();
+    let bytes = response
+        .into_body()
+        .collect()
+        .await
+        .map_err(io::Error::other)?
+        .to_bytes();
+    let body = serde_json::from_slice(&bytes).unwrap_or(serde_json::Value::Null);
+
+    Ok(ApiResponse { status, body })
+}
+
+fn tls_connector(root: &Path) -> io::Result<TlsConnector> {
+    let invalid = |e: &dyn std::fmt::Display| io::Error::new(io::ErrorKind::InvalidData, e.to_string());
+
+    let mut roots = rustls::RootCertStore::empty();
+    for cert in CertificateDer::pem_file_iter(root).map_err(|e| invalid(&e))? {
+        roots
+            .add(cert.map_err(|e| invalid(&e))?)
+            .map_err(|e| invalid(&e))?;
+    }
+
+    let config = rustls::ClientConfig::builder_with_provider(Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()
+    .map_err(|e| invalid(&e))?
+    .with_root_certificates(roots)
+    .with_no_client_auth();
+
+    Ok(TlsConnector::from(Arc::new(config)))
+}
+
+/// Maps a wildcard bind address to the loopback address to connect to.
+pub fn connect_host(host: &str) -> &str {
+    match host {
+        "0.0.0.0" => "127.0.0.1",
+        "::" => "::1",
+        _ => host,
+    }
+}
+
+/// Formats a host for use in a URL authority, bracketing IPv6 literals.
+pub fn host_for_url(host: &str) -> String {
+    let host = connect_host(host);
+    if host.contains(':') {
+        format!("[{}]", host)
+    } else {
+        host.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_connect_host_maps_wildcard() {
+        assert_eq!(connect_host("0.0.0.0"), "127.0.0.1");
+        assert_eq!(connect_host("::"), "::1");
+        assert_eq!(connect_host("localhost"), "localhost");
+    }
+
+    #[test]
+    fn test_host_for_url_brackets_ipv6() {
+        assert_eq!(host_for_url("::1"), "[::1]");
+        assert_eq!(host_for_url("127.0.0.1"), "127.0.0.1");
+    }
+
+    #[tokio::test]
+    async fn test_client_reports_connection_refused() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+
+        let client = Client::new(
+            Endpoint::Tcp {
+                host: "127.0.0.1".to_string(),
+                port,
+            },
+            None,
+        )
+        .unwrap();
+        assert!(client.get("/api/status").await.is_err());
+    }
+}
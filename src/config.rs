@@ -0,0 +1,519 @@
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use pulldown_cmark::Options;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Name of the per-workspace configuration file, read from the workspace root.
+pub const WORKSPACE_CONFIG_FILE: &str = ".mdv.toml";
+
+/// Server-wide sections a workspace file may not override. For `server`
+/// and `security` this also keeps a checked-out repository from widening
+/// what the server exposes.
+const USER_ONLY_SECTIONS: [&str; 3] = ["server", "security", "compression"];
+
+/// Effective configuration. Every field has a default, so config files only
+/// need to list what they change.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub watcher: WatcherConfig,
+    pub files: FilesConfig,
+    pub view: ViewConfig,
+    pub markdown: MarkdownConfig,
+    pub compression: CompressionConfig,
+    pub security: SecurityConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub host: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    /// Which workspace a file belongs to when registered roots are nested.
+    pub nested_workspaces: NestedWorkspaces,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            host: "127.0.0.1".to_string(),
+            port: None,
+            nested_workspaces: NestedWorkspaces::Innermost,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NestedWorkspaces {
+    /// The workspace with the longest root containing the file.
+    #[default]
+    Innermost,
+    /// The workspace with the shortest root containing the file.
+    Outermost,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WatcherMode {
+    /// Periodically scan the workspace. Works on network and container mounts.
+    #[default]
+    Poll,
+    /// Use the platform's file change notifications (inotify, FSEvents, ...).
+    Native,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WatcherConfig {
+    pub mode: WatcherMode,
+    /// At least `MIN_POLL_INTERVAL_MS`.
+    pub poll_interval_ms: u64,
+}
+
+/// Shorter poll intervals make the poll watcher spin.
+pub const MIN_POLL_INTERVAL_MS: u64 = 50;
+
+impl Default for WatcherConfig {
+    fn default() -> Self {
+        Self {
+            mode: WatcherMode::Poll,
+            poll_interval_ms: 500,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FilesConfig {
+    /// Extensions rendered as markdown, compared case-insensitively.
+    pub extensions: Vec<String>,
+    /// Extensionless file names rendered as markdown, e.g. `README`.
+    pub filenames: Vec<String>,
+    /// Glob patterns matched against file and directory names to hide them
+    /// from listings and the watcher, e.g. `node_modules` or `*.draft.md`.
+    pub ignore: Vec<String>,
+}
+
+impl Default for FilesConfig {
+    fn default() -> Self {
+        Self {
+            extensions: ["md", "markdown", "mdown", "mkd", "mdx"]
+                .map(String::from)
+                .to_vec(),
+            filenames: vec!["README".to_string()],
+            ignore: Vec::new(),
+        }
+    }
+}
+
+impl FilesConfig {
+    /// Compiles the file rules. Invalid ignore patterns are rejected when
+    /// the config is loaded, so they are skipped here.
+    pub fn filter(&self) -> FileFilter {
+        let mut ignore = GlobSetBuilder::new();
+        for pattern in &self.ignore {
+            if let Ok(glob) = Glob::new(pattern) {
+                ignore.add(glob);
+            }
+        }
+        let lowercase = |names: &[String]| -> Vec<String> {
+            names
+                .iter()
+                .map(|n| n.trim_start_matches('.').to_lowercase())
+                .collect()
+        };
+        FileFilter {
+            extensions: lowercase(&self.extensions),
+            filenames: lowercase(&self.filenames),
+            ignore: ignore.build().unwrap_or_else(|_| GlobSet::empty()),
+        }
+    }
+}
+
+/// Compiled form of [`FilesConfig`].
+pub struct FileFilter {
+    /// Lowercased, without leading dot.
+    extensions: Vec<String>,
+    /// Lowercased.
+    filenames: Vec<String>,
+    ignore: GlobSet,
+}
+
+impl FileFilter {
+    /// Whether `path` names a markdown file, by extension or by one of the
+    /// configured file names. Both comparisons ignore case.
+    pub fn is_markdown(&self, path: &Path) -> bool {
+        if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
+            let ext = ext.to_lowercase();
+            if self.extensions.contains(&ext) {
+                return true;
+            }
+        }
+        path.file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|name| self.filenames.contains(&name.to_lowercase()))
+    }
+
+    /// Whether an entry called `name` is hidden by an ignore pattern.
+    pub fn is_ignored(&self, name: &str) -> bool {
+        self.ignore.is_match(name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Theme {
+    #[default]
+    Dark,
+    Light,
+}
+
+impl Theme {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Theme::Dark => "dark",
+            Theme::Light => "light",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ViewConfig {
+    pub theme: Theme,
+    /// Documents rendered below a directory listing, in order of
+    /// preference. Matched case-insensitively.
+    pub index_files: Vec<String>,
+    /// Open a directory's index document instead of listing it.
+    pub redirect_to_index: bool,
+}
+
+impl Default for ViewConfig {
+    fn default() -> Self {
+        Self {
+            theme: Theme::default(),
+            index_files: vec!["README.md".to_string(), "index.md".to_string()],
+            redirect_to_index: false,
+        }
+    }
+}
+
+/// Markdown rendering: optional CommonMark extensions and size limit.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MarkdownConfig {
+    pub tables: bool,
+    pub footnotes: bool,
+    pub strikethrough: bool,
+    pub tasklists: bool,
+    pub smart_punctuation: bool,
+    pub heading_attributes: bool,
+    /// Larger files are served as plain text instead of being rendered.
+    pub max_file_size: u64,
+}
+
+impl Default for MarkdownConfig {
+    fn default() -> Self {
+        Self {
+            tables: true,
+            footnotes: true,
+            strikethrough: true,
+            tasklists: true,
+            smart_punctuation: false,
+            heading_attributes: false,
+            max_file_size: 10 * 1024 * 1024,
+        }
+    }
+}
+
+impl MarkdownConfig {
+    pub fn options(&self) -> Options {
+        let mut options = Options::empty();
+        options.set(Options::ENABLE_TABLES, self.tables);
+        options.set(Options::ENABLE_FOOTNOTES, self.footnotes);
+        options.set(Options::ENABLE_STRIKETHROUGH, self.strikethrough);
+        options.set(Options::ENABLE_TASKLISTS, self.tasklists);
+        options.set(Options::ENABLE_SMART_PUNCTUATION, self.smart_punctuation);
+        options.set(Options::ENABLE_HEADING_ATTRIBUTES, self.heading_attributes);
+        options
+    }
+}
+
+/// Response compression (gzip, brotli or zstd, as the client prefers).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CompressionConfig {
+    pub enabled: bool,
+    /// Responses smaller than this many bytes are sent uncompressed.
+    pub min_size: u16,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_size: 1024,
+        }
+    }
+}
+
+/// Same settings as the `--allow-origin`, `--allow-root` and `--deny-root`
+/// flags; values from the config file and the command line are combined.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SecurityConfig {
+    pub allow_origins: Vec<String>,
+    pub allow_roots: Vec<PathBuf>,
+    pub deny_roots: Vec<PathBuf>,
+}
+
+/// Default location of the user configuration file
+/// (`~/.config/mdv/config.toml` on Linux).
+pub fn user_config_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("mdv").join("config.toml"))
+}
+
+impl Config {
+    /// Loads the user configuration from `path`, or from the default
+    /// location when `path` is `None`. A missing default file yields the
+    /// built-in defaults; an explicitly given file must exist.
+    pub fn load(path: Option<&Path>) -> Result<Self, String> {
+        let table = match path {
+            Some(path) => read_table(path)?
+                .ok_or_else(|| format!("Config file {} does not exist", path.display()))?,
+            None => match user_config_path() {
+                Some(path) => read_table(&path)?.unwrap_or_default(),
+                None => toml::Table::new(),
+            },
+        };
+        Self::from_table(table)
+    }
+
+    /// Returns this configuration overlaid with the workspace's
+    /// `.mdv.toml`, if it has one. Server-wide sections (`[server]`,
+    /// `[security]`, `[compression]`) of workspace files are ignored.
+    pub fn for_workspace(&self, root: &Path) -> Result<Self, String> {
+        let Some(mut overlay) = read_table(&root.join(WORKSPACE_CONFIG_FILE))? else {
+            return Ok(self.clone());
+        };
+        for section in USER_ONLY_SECTIONS {
+            overlay.remove(section);
+        }
+
+        let mut table = toml::Table::try_from(self).map_err(|e| e.to_string())?;
+        merge_tables(&mut table, overlay);
+        Self::from_table(table)
+    }
+
+    fn from_table(table: toml::Table) -> Result<Self, String> {
+        let config: Config = table.try_into().map_err(|e: toml::de::Error| e.to_string())?;
+        for pattern in &config.files.ignore {
+            Glob::new(pattern).map_err(|e| format!("Invalid ignore pattern: {}", e))?;
+        }
+        if config.watcher.poll_interval_ms < MIN_POLL_INTERVAL_MS {
+            return Err(format!(
+                "watcher.poll_interval_ms must be at least {} (got {})",
+                MIN_POLL_INTERVAL_MS, config.watcher.poll_interval_ms
+            ));
+        }
+        Ok(config)
+    }
+
+    /// Renders the configuration as TOML, for `mdv config show`.
+    pub fn to_toml(&self) -> String {
+        toml::to_string_pretty(self).unwrap_or_default()
+    }
+}
+
+/// Reads a TOML file, returning `None` if it does not exist.
+fn read_table(path: &Path) -> Result<Option<toml::Table>, String> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("Cannot read {}: {}", path.display(), e)),
+    };
+    text.parse::<toml::Table>()
+        .map(Some)
+        .map_err(|e| format!("Invalid config {}: {}", path.display(), e))
+}
+
+/// Recursively overlays `overlay` onto `base`; tables are merged key by
+/// key, any other value replaces the one in `base`.
+fn merge_tables(base: &mut toml::Table, overlay: toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base_table)), toml::Value::Table(table)) => {
+                merge_tables(base_table, table);
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn write(path: &Path, text: &str) {
+        fs::write(path, text).unwrap();
+    }
+
+    #[test]
+    fn test_load_partial_file_keeps_defaults() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("config.toml");
+        write(&path, "[server]\nport = 4000\n\n[markdown]\ntables = false\n");
+
+        let config = Config::load(Some(&path)).unwrap();
+        assert_eq!(config.server.port, Some(4000));
+        assert_eq!(config.server.host, "127.0.0.1");
+        assert!(!config.markdown.tables);
+        assert!(config.markdown.footnotes);
+        assert_eq!(config.watcher.poll_interval_ms, 500);
+    }
+
+    #[test]
+    fn test_load_nested_workspaces() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("config.toml");
+        assert_eq!(Config::default().server.nested_workspaces, NestedWorkspaces::Innermost);
+
+        write(&path, "[server]\nnested_workspaces = \"outermost\"\n");
+        let config = Config::load(Some(&path)).unwrap();
+        assert_eq!(config.server.nested_workspaces, NestedWorkspaces::Outermost);
+
+        write(&path, "[server]\nnested_workspaces = \"middle\"\n");
+        assert!(Config::load(Some(&path)).is_err());
+    }
+
+    #[test]
+    fn test_load_index_files() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("config.toml");
+        assert_eq!(Config::default().view.index_files, ["README.md", "index.md"]);
+
+        write(&path, "[view]\nindex_files = [\"_index.md\"]\nredirect_to_index = true\n");
+        let config = Config::load(Some(&path)).unwrap();
+        assert_eq!(config.view.index_files, ["_index.md"]);
+        assert!(config.view.redirect_to_index);
+    }
+
+    #[test]
+    fn test_load_rejects_short_poll_interval() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("config.toml");
+        write(&path, "[watcher]\npoll_interval_ms = 0\n");
+        assert!(Config::load(Some(&path)).is_err());
+
+        write(&path, &format!("[watcher]\npoll_interval_ms = {}\n", MIN_POLL_INTERVAL_MS));
+        assert!(Config::load(Some(&path)).is_ok());
+    }
+
+    #[test]
+    fn test_load_rejects_unknown_keys() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("config.toml");
+        write(&path, "[server]\nprot = 4000\n");
+
+        assert!(Config::load(Some(&path)).is_err());
+    }
+
+    #[test]
+    fn test_load_rejects_invalid_ignore_pattern() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("config.toml");
+        write(&path, "[files]\nignore = [\"[oops\"]\n");
+
+        assert!(Config::load(Some(&path)).is_err());
+    }
+
+    #[test]
+    fn test_load_missing_explicit_file_fails() {
+        let temp = TempDir::new().unwrap();
+        assert!(Config::load(Some(&temp.path().join("nope.toml"))).is_err());
+    }
+
+    #[test]
+    fn test_workspace_overlay() {
+        let temp = TempDir::new().unwrap();
+        write(
+            &temp.path().join(WORKSPACE_CONFIG_FILE),
+            "[view]\ntheme = \"light\"\n\n[watcher]\nmode = \"native\"\n",
+        );
+
+        let mut user = Config::default();
+        user.watcher.poll_interval_ms = 1000;
+        let config = user.for_workspace(temp.path()).unwrap();
+        assert_eq!(config.view.theme, Theme::Light);
+        assert_eq!(config.watcher.mode, WatcherMode::Native);
+        assert_eq!(config.watcher.poll_interval_ms, 1000);
+    }
+
+    #[test]
+    fn test_workspace_cannot_override_server_wide_sections() {
+        let temp = TempDir::new().unwrap();
+        write(
+            &temp.path().join(WORKSPACE_CONFIG_FILE),
+            "[security]\nallow_roots = [\"/\"]\n\n[server]\nhost = \"0.0.0.0\"\n\n[compression]\nenabled = false\n",
+        );
+
+        let config = Config::default().for_workspace(temp.path()).unwrap();
+        assert!(config.security.allow_roots.is_empty());
+        assert_eq!(config.server.host, "127.0.0.1");
+        assert!(config.compression.enabled);
+    }
+
+    #[test]
+    fn test_file_filter_defaults() {
+        let filter = FilesConfig::default().filter();
+        for name in ["a.md", "a.markdown", "a.mdown", "a.mkd", "a.mdx", "A.MD", "Notes.Markdown"] {
+            assert!(filter.is_markdown(Path::new(name)), "{}", name);
+        }
+        assert!(filter.is_markdown(Path::new("docs/README")));
+        assert!(filter.is_markdown(Path::new("docs/readme")));
+        assert!(!filter.is_markdown(Path::new("docs/README.txt")));
+        assert!(!filter.is_markdown(Path::new("Makefile")));
+        assert!(!filter.is_markdown(Path::new("md")));
+    }
+
+    #[test]
+    fn test_file_filter_custom_extensions() {
+        let files = FilesConfig {
+            extensions: vec![".TXT".to_string()],
+            filenames: Vec::new(),
+            ignore: Vec::new(),
+        };
+        let filter = files.filter();
+        assert!(filter.is_markdown(Path::new("notes.txt")));
+        assert!(!filter.is_markdown(Path::new("notes.md")));
+        assert!(!filter.is_markdown(Path::new("README")));
+    }
+
+    #[test]
+    fn test_file_filter() {
+        let files = FilesConfig {
+            ignore: vec!["node_modules".to_string(), "*.draft.md".to_string()],
+            ..Default::default()
+        };
+        let filter = files.filter();
+        assert!(filter.is_markdown(Path::new("a/readme.md")));
+        assert!(!filter.is_markdown(Path::new("a/readme.txt")));
+        assert!(filter.is_ignored("node_modules"));
+        assert!(filter.is_ignored("notes.draft.md"));
+        assert!(!filter.is_ignored("notes.md"));
+    }
+
+    #[test]
+    fn test_to_toml_roundtrip() {
+        let config = Config::default();
+        let parsed = Config::from_table(config.to_toml().parse().unwrap()).unwrap();
+        assert_eq!(parsed.files.extensions, config.files.extensions);
+    }
+}
@@ -0,0 +1,100 @@
+use std::{
+    ffi::OsString,
+    fs::File,
+    io,
+    path::Path,
+    process::{Child, Stdio},
+    time::Duration,
+};
+
+use crate::{runtime, Args};
+
+/// How long `--daemon` waits for the background server to come up.
+const START_WAIT: Duration = Duration::from_secs(5);
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Starts `mdv <args>` detached from the terminal, with its output
+/// appended to `log`. On Unix the child runs in its own session so that
+/// closing the terminal or editor that started it does not stop it.
+pub fn spawn_detached(args: &[OsString], log: &Path) -> io::Result<Child> {
+    if let Some(parent) = log.parent() {
+        crate::runtime::create_private_dir(parent)?;
+    }
+    let mut options = File::options();
+    options.create(true).append(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600).custom_flags(libc::O_NOFOLLOW);
+    }
+    let log = options.open(log)?;
+
+    let exe = std::env::current_exe()?;
+    let mut command = std::process::Command::new(exe);
+    command
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log);
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        // SAFETY: setsid is async-signal-safe and touches no parent state.
+        unsafe {
+            command.pre_exec(|| {
+                if libc::setsid() == -1 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+    }
+    command.spawn()
+}
+
+/// Implements `--daemon`: re-executes the current command line without
+/// the flag in the background and waits until the server has written its
+/// runtime file. Refuses to start when the instance is already running.
+pub async fn start(args: &Args) -> Result<(), String> {
+    let dir = runtime::runtime_dir();
+    let instance = args.instance_name();
+
+    // Probe the lock so the caller gets a direct answer; the child takes it
+    // for real, which also settles races between concurrent `--daemon`s.
+    match runtime::lock_instance(&dir, &instance) {
+        Ok(Some(_)) => {}
+        Ok(None) => return Err(runtime::describe_running(&dir, &instance)),
+        Err(e) => return Err(format!("Cannot lock {}: {}", dir.display(), e)),
+    }
+
+    let child_args: Vec<OsString> = std::env::args_os()
+        .skip(1)
+        .filter(|arg| arg != "--daemon")
+        .collect();
+    let log = runtime::log_path(&dir, &instance);
+    let mut child = spawn_detached(&child_args, &log)
+        .map_err(|e| format!("Cannot start mdv server: {}", e))?;
+
+    let deadline = tokio::time::Instant::now() + START_WAIT;
+    while tokio::time::Instant::now() < deadline {
+        tokio::time::sleep(POLL_INTERVAL).await;
+        if let Ok(Some(status)) = child.try_wait() {
+            return Err(format!(
+                "mdv server exited with {} (see {})",
+                status,
+                log.display()
+            ));
+        }
+        if let Some(info) = runtime::read_info(&dir, &instance) {
+            if info.pid == child.id() {
+                println!("mdv server started in the background (pid {})", info.pid);
+                println!("Log: {}", log.display());
+                return Ok(());
+            }
+        }
+    }
+    Err(format!(
+        "Timed out waiting for mdv server to start (see {})",
+        log.display()
+    ))
+}
@@ -0,0 +1,280 @@
+use askama::Template;
+use axum::{extract::State, response::Response};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::{config::FileFilter, format_datetime, recent, render_page, security, AppState, Workspace};
+
+/// Number of files listed per section across all workspaces.
+const RECENT_FILES: usize = 10;
+
+/// How long a workspace scan is reused before the tree is walked again.
+const SCAN_TTL: Duration = Duration::from_secs(30);
+
+/// Directory entries visited per scan. Larger workspaces, such as a whole
+/// home directory, are only counted partially.
+const SCAN_LIMIT: usize = 20_000;
+
+struct WorkspaceRow {
+    id: String,
+    name: String,
+    alias: String,
+    path: String,
+    url: String,
+    trusted: bool,
+    file_count: usize,
+    /// The scan stopped at `SCAN_LIMIT` entries.
+    truncated: bool,
+    last_change: String,
+    watcher: &'static str,
+}
+
+struct RecentFile {
+    path: String,
+    url: String,
+    workspace_name: String,
+    time: String,
+}
+
+#[derive(Template)]
+#[template(path = "index.html")]
+struct DashboardTemplate {
+    workspaces: Vec<WorkspaceRow>,
+    /// Tracked since the server started.
+    viewed: Vec<RecentFile>,
+    /// Reported by the watchers since the server started, like `/api/recent`.
+    changed: Vec<RecentFile>,
+    /// By modification time, found by scanning the workspaces.
+    newest: Vec<RecentFile>,
+    theme: &'static str,
+    csp_nonce: String,
+}
+
+/// Markdown files found by walking a workspace.
+#[derive(Default)]
+struct WorkspaceScan {
+    file_count: usize,
+    truncated: bool,
+    /// Most recently modified first, at most `RECENT_FILES` of them.
+    recent: Vec<(PathBuf, SystemTime)>,
+}
+
+/// The last scan of a workspace, reused for `SCAN_TTL`.
+#[derive(Default)]
+pub struct ScanCache {
+    last: Mutex<Option<(Instant, Arc<WorkspaceScan>)>>,
+}
+
+impl ScanCache {
+    /// Blocking. Concurrent callers wait for one scan instead of each
+    /// walking the tree.
+    fn get(&self, root: &Path, filter: &FileFilter) -> Arc<WorkspaceScan> {
+        let mut last = self.last.lock().unwrap();
+        if let Some((scanned_at, scan)) = last.as_ref() {
+            if scanned_at.elapsed() < SCAN_TTL {
+                return scan.clone();
+            }
+        }
+        let scan = Arc::new(scan_workspace(root, filter, SCAN_LIMIT));
+        *last = Some((Instant::now(), scan.clone()));
+        scan
+    }
+}
+
+fn keep_newest(files: &mut Vec<(PathBuf, SystemTime)>) {
+    files.sort_by_key(|(_, modified)| std::cmp::Reverse(*modified));
+    files.truncate(RECENT_FILES);
+}
+
+/// Walks `root` the way directory listings do: hidden and ignored entries
+/// are skipped. Symlinked directories are not followed. Stops after
+/// `limit` entries. Blocking.
+fn scan_workspace(root: &Path, filter: &FileFilter, limit: usize) -> WorkspaceScan {
+    let mut scan = WorkspaceScan::default();
+    let mut dirs = vec![root.to_path_buf()];
+    let mut visited = 0;
+
+    while let Some(dir) = dirs.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.filter_map(|e| e.ok()) {
+            visited += 1;
+            if visited > limit {
+                scan.truncated = true;
+                keep_newest(&mut scan.recent);
+                return scan;
+            }
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.starts_with('.') || filter.is_ignored(&name) {
+                continue;
+            }
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            let path = entry.path();
+            if file_type.is_dir() {
+                dirs.push(path);
+            } else if filter.is_markdown(&path) {
+                scan.file_count += 1;
+                if let Ok(modified) = entry.metadata().and_then(|m| m.modified()) {
+                    scan.recent.push((path, modified));
+                    if scan.recent.len() >= RECENT_FILES * 4 {
+                        keep_newest(&mut scan.recent);
+                    }
+                }
+            }
+        }
+    }
+
+    keep_newest(&mut scan.recent);
+    scan
+}
+
+// Dashboard: registered workspaces and recently viewed and changed files
+pub async fn handle_root(State(state): State<AppState>) -> Response {
+    let inner = state.inner.read().await;
+    let files = |lists: Vec<(&Workspace, Vec<recent::RecentEntry>)>| -> Vec<RecentFile> {
+        recent::merge(lists, RECENT_FILES)
+            .into_iter()
+            .map(|(w, entry)| RecentFile {
+                url: format!("/view/{}/{}", w.url_key(), entry.path),
+                path: entry.path,
+                workspace_name: w.name.clone(),
+                time: format_datetime(entry.time),
+            })
+            .collect()
+    };
+    let viewed = files(inner.workspaces.values().map(|w| (w, w.recent.viewed())).collect());
+    let changed = files(inner.workspaces.values().map(|w| (w, w.recent.changed())).collect());
+    let workspaces: Vec<(WorkspaceRow, PathBuf, FileFilter, Arc<ScanCache>)> = inner
+        .workspaces
+        .values()
+        .map(|w| {
+            let row = WorkspaceRow {
+                id: w.id.clone(),
+                name: w.name.clone(),
+                alias: w.alias.clone().unwrap_or_default(),
+                path: w.root_dir.display().to_string(),
+                url: format!("/view/{}", w.url_key()),
+                trusted: w.trusted,
+                file_count: 0,
+                truncated: false,
+                last_change: "-".to_string(),
+                watcher: w.watcher.as_ref().map_or("stopped", |watcher| watcher.state()),
+            };
+            (row, w.root_dir.clone(), w.config.files.filter(), w.scan.clone())
+        })
+        .collect();
+    drop(inner);
+
+    let scanned = tokio::task::spawn_blocking(move || {
+        let mut rows = Vec::new();
+        let mut recent = Vec::new();
+        for (mut row, root, filter, cache) in workspaces {
+            let scan = cache.get(&root, &filter);
+            row.file_count = scan.file_count;
+            row.truncated = scan.truncated;
+            if let Some((_, modified)) = scan.recent.first() {
+                row.last_change = format_datetime(*modified);
+            }
+            for (path, modified) in scan.recent.iter().cloned() {
+                let relative = path.strip_prefix(&root).unwrap_or(&path).to_string_lossy().to_string();
+                let file = RecentFile {
+                    url: format!("{}/{}", row.url, relative),
+                    path: relative,
+                    workspace_name: row.name.clone(),
+                    time: format_datetime(modified),
+                };
+                recent.push((file, modified));
+            }
+            rows.push(row);
+        }
+        rows.sort_by_key(|row| row.name.to_lowercase());
+        recent.sort_by_key(|(_, modified)| std::cmp::Reverse(*modified));
+        recent.truncate(RECENT_FILES);
+        (rows, recent.into_iter().map(|(file, _)| file).collect())
+    })
+    .await;
+    let (workspaces, newest) = scanned.unwrap_or_default();
+
+    let template = DashboardTemplate {
+        workspaces,
+        viewed,
+        changed,
+        newest,
+        theme: state.config.view.theme.as_str(),
+        csp_nonce: security::generate_nonce(),
+    };
+    render_page(&template, &template.csp_nonce)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_scan_workspace() {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path();
+        fs::create_dir_all(root.join("docs")).unwrap();
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::create_dir_all(root.join("node_modules")).unwrap();
+        fs::write(root.join("README.md"), "# r").unwrap();
+        fs::write(root.join("docs").join("guide.md"), "# g").unwrap();
+        fs::write(root.join("docs").join("image.png"), "").unwrap();
+        fs::write(root.join(".git").join("notes.md"), "").unwrap();
+        fs::write(root.join("node_modules").join("readme.md"), "").unwrap();
+
+        let files = crate::config::FilesConfig {
+            ignore: vec!["node_modules".to_string()],
+            ..Default::default()
+        };
+        let scan = scan_workspace(root, &files.filter(), SCAN_LIMIT);
+        assert_eq!(scan.file_count, 2);
+        assert!(!scan.truncated);
+        assert_eq!(scan.recent.len(), 2);
+        assert!(scan.recent[0].1 >= scan.recent[1].1);
+    }
+
+    #[test]
+    fn test_scan_workspace_keeps_newest() {
+        let temp = TempDir::new().unwrap();
+        let base = SystemTime::now() - std::time::Duration::from_secs(3600);
+        for i in 0..RECENT_FILES * 5 {
+            let path = temp.path().join(format!("{}.md", i));
+            let file = fs::File::create(&path).unwrap();
+            file.set_modified(base + std::time::Duration::from_secs(i as u64)).unwrap();
+        }
+
+        let scan = scan_workspace(temp.path(), &crate::config::FilesConfig::default().filter(), SCAN_LIMIT);
+        assert_eq!(scan.file_count, RECENT_FILES * 5);
+        assert_eq!(scan.recent.len(), RECENT_FILES);
+        let newest = temp.path().join(format!("{}.md", RECENT_FILES * 5 - 1));
+        assert_eq!(scan.recent[0].0, newest);
+    }
+
+    #[test]
+    fn test_scan_workspace_limit_and_cache() {
+        let temp = TempDir::new().unwrap();
+        for i in 0..5 {
+            fs::write(temp.path().join(format!("{}.md", i)), "").unwrap();
+        }
+        let filter = crate::config::FilesConfig::default().filter();
+
+        let scan = scan_workspace(temp.path(), &filter, 3);
+        assert!(scan.truncated);
+        assert_eq!(scan.file_count, 3);
+
+        let cache = ScanCache::default();
+        let first = cache.get(temp.path(), &filter);
+        fs::write(temp.path().join("new.md"), "").unwrap();
+        assert!(Arc::ptr_eq(&first, &cache.get(temp.path(), &filter)));
+        assert_eq!(first.file_count, 5);
+    }
+}
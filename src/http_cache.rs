@@ -0,0 +1,334 @@
+use axum::{
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use std::{
+    collections::HashMap,
+    fs::Metadata,
+    path::{Path, PathBuf},
+    sync::{Arc, LazyLock, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Upper bound on cached rendered documents; the cache is cleared when full.
+const RENDER_CACHE_CAPACITY: usize = 512;
+
+/// Changes on every server start, so rendered pages cached by browsers are
+/// revalidated after a restart that may have changed templates or settings.
+static BOOT_ID: LazyLock<u128> = LazyLock::new(|| nanos(SystemTime::now()));
+
+fn nanos(time: SystemTime) -> u128 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0)
+}
+
+/// Cache validators for a response derived from a single file.
+pub struct Validators {
+    etag: String,
+    last_modified: Option<SystemTime>,
+}
+
+impl Validators {
+    /// Strong validators for serving a file's bytes as-is.
+    pub fn for_file(metadata: &Metadata) -> Self {
+        let modified = metadata.modified().ok();
+        Self {
+            etag: format!(
+                "\"{:x}-{:x}\"",
+                metadata.len(),
+                modified.map(nanos).unwrap_or(0)
+            ),
+            last_modified: modified,
+        }
+    }
+
+    /// Weak validators for a page rendered from a file. `variant` folds in
+    /// per-request rendering inputs such as the workspace trust setting.
+    pub fn for_rendered(metadata: &Metadata, variant: &str) -> Self {
+        let modified = metadata.modified().ok();
+        Self {
+            etag: format!(
+                "W/\"{:x}-{:x}-{:x}-{}\"",
+                metadata.len(),
+                modified.map(nanos).unwrap_or(0),
+                *BOOT_ID,
+                variant
+            ),
+            last_modified: modified,
+        }
+    }
+
+    /// Evaluates `If-None-Match` / `If-Modified-Since`. When both are
+    /// present, `If-None-Match` decides (RFC 9110, section 13.2.2).
+    pub fn is_not_modified(&self, headers: &HeaderMap) -> bool {
+        if let Some(value) = headers.get(header::IF_NONE_MATCH) {
+            let Ok(value) = value.to_str() else { return false };
+            return value
+                .split(',')
+                .map(str::trim)
+                .any(|tag| tag == "*" || weak_eq(tag, &self.etag));
+        }
+
+        let (Some(since), Some(modified)) = (
+            headers
+                .get(header::IF_MODIFIED_SINCE)
+                .and_then(|v| v.to_str().ok())
+                .and_then(parse_http_date),
+            self.last_modified,
+        ) else {
+            return false;
+        };
+        // HTTP dates have one-second resolution.
+        nanos(modified) / 1_000_000_000 <= nanos(since) / 1_000_000_000
+    }
+
+    /// Evaluates `If-Range`: a `Range` header may only be honoured when
+    /// this is true. Entity tags must match strongly, dates exactly.
+    pub fn if_range_matches(&self, headers: &HeaderMap) -> bool {
+        let Some(value) = headers.get(header::IF_RANGE).and_then(|v| v.to_str().ok()) else {
+            return true;
+        };
+        if value.starts_with('"') || value.starts_with("W/") {
+            return !self.etag.starts_with("W/") && value == self.etag;
+        }
+        match (parse_http_date(value), self.last_modified) {
+            (Some(date), Some(modified)) => nanos(date) / 1_000_000_000 == nanos(modified) / 1_000_000_000,
+            _ => false,
+        }
+    }
+
+    /// Adds `ETag`, `Last-Modified` and `Cache-Control: no-cache` (store,
+    /// but revalidate before every use) to `response`.
+    pub fn apply(&self, response: &mut Response) {
+        let headers = response.headers_mut();
+        if let Ok(etag) = HeaderValue::from_str(&self.etag) {
+            headers.insert(header::ETAG, etag);
+        }
+        if let Some(modified) = self.last_modified {
+            if let Ok(value) = HeaderValue::from_str(&format_http_date(modified)) {
+                headers.insert(header::LAST_MODIFIED, value);
+            }
+        }
+        headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+    }
+
+    /// A bodyless `304 Not Modified` carrying the validators.
+    pub fn not_modified(&self) -> Response {
+        let mut response = StatusCode::NOT_MODIFIED.into_response();
+        self.apply(&mut response);
+        response
+    }
+}
+
+/// Weakens a strong `ETag` on a content-coded response: the tag names the
+/// file's exact bytes, which a compressed body no longer has (RFC 9110,
+/// section 8.8.3). Runs outside the compression layer.
+pub async fn weaken_encoded_etag(mut response: Response) -> Response {
+    let headers = response.headers_mut();
+    if !headers.contains_key(header::CONTENT_ENCODING) {
+        return response;
+    }
+    let weak = headers
+        .get(header::ETAG)
+        .and_then(|etag| etag.to_str().ok())
+        .filter(|etag| etag.starts_with('"'))
+        .and_then(|etag| HeaderValue::from_str(&format!("W/{}", etag)).ok());
+    if let Some(weak) = weak {
+        headers.insert(header::ETAG, weak);
+    }
+    response
+}
+
+/// Weak comparison of two entity tags: equal once `W/` prefixes are removed.
+fn weak_eq(a: &str, b: &str) -> bool {
+    a.trim_start_matches("W/") == b.trim_start_matches("W/")
+}
+
+pub fn format_http_date(time: SystemTime) -> String {
+    let datetime: DateTime<Utc> = time.into();
+    datetime.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+fn parse_http_date(value: &str) -> Option<SystemTime> {
+    DateTime::parse_from_rfc2822(value).ok().map(SystemTime::from)
+}
+
+/// Rendered HTML of markdown documents, keyed by canonical path and
+/// rendering variant (workspace, trust and link base), and checked against
+/// the file's size and mtime. A document shown both on its own and as a
+/// directory's index keeps one entry per view. Watchers also invalidate
+/// entries as soon as a file changes.
+#[derive(Default)]
+pub struct RenderCache {
+    entries: Mutex<HashMap<(PathBuf, String), CachedRender>>,
+}
+
+struct CachedRender {
+    modified: Option<SystemTime>,
+    len: u64,
+    html: Arc<str>,
+}
+
+impl RenderCache {
+    pub fn get(&self, path: &Path, metadata: &Metadata, variant: &str) -> Option<Arc<str>> {
+        let entries = self.entries.lock().unwrap();
+        let entry = entries.get(&(path.to_path_buf(), variant.to_string()))?;
+        (entry.modified == metadata.modified().ok() && entry.len == metadata.len()).then(|| entry.html.clone())
+    }
+
+    pub fn insert(&self, path: PathBuf, metadata: &Metadata, variant: &str, html: Arc<str>) {
+        let mut entries = self.entries.lock().unwrap();
+        let key = (path, variant.to_string());
+        if entries.len() >= RENDER_CACHE_CAPACITY && !entries.contains_key(&key) {
+            entries.clear();
+        }
+        entries.insert(
+            key,
+            CachedRender {
+                modified: metadata.modified().ok(),
+                len: metadata.len(),
+                html,
+            },
+        );
+    }
+
+    /// Drops every variant of `path`.
+    pub fn invalidate(&self, path: &Path) {
+        self.entries.lock().unwrap().retain(|(cached, _), _| cached != path);
+    }
+
+    /// Drops every entry below `dir`, e.g. when a workspace is removed.
+    pub fn invalidate_dir(&self, dir: &Path) {
+        self.entries.lock().unwrap().retain(|(path, _), _| !path.starts_with(dir));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn headers(name: header::HeaderName, value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_if_none_match() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("a.md");
+        fs::write(&path, "# a").unwrap();
+        let validators = Validators::for_file(&fs::metadata(&path).unwrap());
+
+        let etag = validators.etag.clone();
+        assert!(validators.is_not_modified(&headers(header::IF_NONE_MATCH, &etag)));
+        assert!(validators.is_not_modified(&headers(header::IF_NONE_MATCH, &format!("W/{}", etag))));
+        assert!(validators.is_not_modified(&headers(header::IF_NONE_MATCH, &format!("\"x\", {}", etag))));
+        assert!(validators.is_not_modified(&headers(header::IF_NONE_MATCH, "*")));
+        assert!(!validators.is_not_modified(&headers(header::IF_NONE_MATCH, "\"other\"")));
+        assert!(!validators.is_not_modified(&HeaderMap::new()));
+    }
+
+    #[test]
+    fn test_if_range() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("a.bin");
+        fs::write(&path, "0123456789").unwrap();
+        let metadata = fs::metadata(&path).unwrap();
+        let validators = Validators::for_file(&metadata);
+
+        let etag = validators.etag.clone();
+        assert!(validators.if_range_matches(&HeaderMap::new()));
+        assert!(validators.if_range_matches(&headers(header::IF_RANGE, &etag)));
+        assert!(!validators.if_range_matches(&headers(header::IF_RANGE, &format!("W/{}", etag))));
+        assert!(!validators.if_range_matches(&headers(header::IF_RANGE, "\"other\"")));
+        let date = format_http_date(metadata.modified().unwrap());
+        assert!(validators.if_range_matches(&headers(header::IF_RANGE, &date)));
+    }
+
+    #[test]
+    fn test_if_modified_since() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("a.md");
+        fs::write(&path, "# a").unwrap();
+        let metadata = fs::metadata(&path).unwrap();
+        let validators = Validators::for_file(&metadata);
+
+        let modified = metadata.modified().unwrap();
+        let same = format_http_date(modified);
+        let earlier = format_http_date(modified - std::time::Duration::from_secs(60));
+        assert!(validators.is_not_modified(&headers(header::IF_MODIFIED_SINCE, &same)));
+        assert!(!validators.is_not_modified(&headers(header::IF_MODIFIED_SINCE, &earlier)));
+        assert!(!validators.is_not_modified(&headers(header::IF_MODIFIED_SINCE, "garbage")));
+    }
+
+    #[test]
+    fn test_etag_changes_with_content() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("a.md");
+        fs::write(&path, "# a").unwrap();
+        let before = Validators::for_rendered(&fs::metadata(&path).unwrap(), "u");
+        fs::write(&path, "# changed").unwrap();
+        let after = Validators::for_rendered(&fs::metadata(&path).unwrap(), "u");
+        assert_ne!(before.etag, after.etag);
+        assert!(before.etag.starts_with("W/"));
+    }
+
+    #[test]
+    fn test_render_cache() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("a.md");
+        fs::write(&path, "# a").unwrap();
+        let metadata = fs::metadata(&path).unwrap();
+        let cache = RenderCache::default();
+
+        cache.insert(path.clone(), &metadata, "ws:u", Arc::from("<h1>a</h1>"));
+        assert_eq!(cache.get(&path, &metadata, "ws:u").as_deref(), Some("<h1>a</h1>"));
+        assert!(cache.get(&path, &metadata, "ws:t").is_none());
+
+        fs::write(&path, "# longer").unwrap();
+        assert!(cache.get(&path, &fs::metadata(&path).unwrap(), "ws:u").is_none());
+
+        cache.invalidate_dir(temp.path());
+        assert!(cache.get(&path, &metadata, "ws:u").is_none());
+    }
+
+    #[test]
+    fn test_render_cache_keeps_each_variant() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("README.md");
+        fs::write(&path, "# a").unwrap();
+        let metadata = fs::metadata(&path).unwrap();
+        let cache = RenderCache::default();
+
+        // The document on its own, then as its directory's index.
+        cache.insert(path.clone(), &metadata, "ws:u", Arc::from("page"));
+        cache.insert(path.clone(), &metadata, "ws:u:/view/ws/", Arc::from("index"));
+        assert_eq!(cache.get(&path, &metadata, "ws:u").as_deref(), Some("page"));
+        assert_eq!(cache.get(&path, &metadata, "ws:u:/view/ws/").as_deref(), Some("index"));
+
+        cache.invalidate(&path);
+        assert!(cache.get(&path, &metadata, "ws:u").is_none());
+        assert!(cache.get(&path, &metadata, "ws:u:/view/ws/").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_weaken_encoded_etag() {
+        let response = |encoding: Option<&'static str>| {
+            let mut response = (StatusCode::OK, [(header::ETAG, "\"abc\"")], "body").into_response();
+            if let Some(encoding) = encoding {
+                response.headers_mut().insert(header::CONTENT_ENCODING, HeaderValue::from_static(encoding));
+            }
+            response
+        };
+
+        let plain = weaken_encoded_etag(response(None)).await;
+        assert_eq!(plain.headers()[header::ETAG], "\"abc\"");
+        let encoded = weaken_encoded_etag(response(Some("br"))).await;
+        assert_eq!(encoded.headers()[header::ETAG], "W/\"abc\"");
+    }
+}
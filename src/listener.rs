@@ -0,0 +1,228 @@
+use rustls_pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer};
+use std::{
+    io,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+};
+use tokio_rustls::{rustls, server::TlsStream, TlsAcceptor};
+
+/// Time allowed for a client to complete the TLS handshake.
+const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Binds a Unix domain socket readable and writable by the owner only.
+/// A stale socket file left behind by a previous run is replaced, but a
+/// socket that still accepts connections is reported as in use.
+#[cfg(unix)]
+pub fn bind_unix_socket(path: &Path) -> io::Result<tokio::net::UnixListener> {
+    if path.exists() {
+        if std::os::unix::net::UnixStream::connect(path).is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                "another server is listening on this socket",
+            ));
+        }
+        std::fs::remove_file(path)?;
+    }
+
+    let listener = tokio::net::UnixListener::bind(path)?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    Ok(listener)
+}
+
+/// TCP listener that completes TLS handshakes before handing connections
+/// to axum. Handshakes run in their own tasks so a slow client cannot stall
+/// `accept` for everyone else.
+pub struct TlsListener {
+    local_addr: SocketAddr,
+    rx: mpsc::Receiver<(TlsStream<TcpStream>, SocketAddr)>,
+}
+
+impl TlsListener {
+    pub fn new(mut tcp: TcpListener, config: Arc<rustls::ServerConfig>) -> io::Result<Self> {
+        let local_addr = tcp.local_addr()?;
+        let acceptor = TlsAcceptor::from(config);
+        let (tx, rx) = mpsc::channel(64);
+
+        tokio::spawn(async move {
+            loop {
+                let (stream, addr) = axum::serve::Listener::accept(&mut tcp).await;
+                if tx.is_closed() {
+                    break;
+                }
+                let acceptor = acceptor.clone();
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    let handshake = tokio::time::timeout(TLS_HANDSHAKE_TIMEOUT, acceptor.accept(stream));
+                    if let Ok(Ok(tls)) = handshake.await {
+                        let _ = tx.send((tls, addr)).await;
+                    }
+                });
+            }
+        });
+
+        Ok(Self { local_addr, rx })
+    }
+}
+
+impl axum::serve::Listener for TlsListener {
+    type Io = TlsStream<TcpStream>;
+    type Addr = SocketAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        match self.rx.recv().await {
+            Some(conn) => conn,
+            // The accept loop only ends once this listener is dropped.
+            None => std::future::pending().await,
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        Ok(self.local_addr)
+    }
+}
+
+/// Loads a PEM certificate chain and private key into a rustls config.
+pub fn load_tls_config(cert_path: &Path, key_path: &Path) -> io::Result<Arc<rustls::ServerConfig>> {
+    let invalid = |e: &dyn std::fmt::Display| io::Error::new(io::ErrorKind::InvalidData, e.to_string());
+
+    let certs = CertificateDer::pem_file_iter(cert_path)
+        .map_err(|e| invalid(&e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| invalid(&e))?;
+    let key = PrivateKeyDer::from_pem_file(key_path).map_err(|e| invalid(&e))?;
+
+    let config = rustls::ServerConfig::builder_with_provider(Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()
+    .map_err(|e| invalid(&e))?
+    .with_no_client_auth()
+    .with_single_cert(certs, key)
+    .map_err(|e| invalid(&e))?;
+
+    Ok(Arc::new(config))
+}
+
+/// Returns the certificate and key paths inside `dir`, generating a
+/// self-signed pair for `hosts` on first use. Existing files are reused so
+/// the certificate only needs to be trusted in the browser once, unless
+/// they were issued for other hosts.
+pub fn ensure_self_signed_cert(dir: &Path, hosts: &[String]) -> io::Result<(PathBuf, PathBuf)> {
+    let cert_path = dir.join("cert.pem");
+    let key_path = dir.join("key.pem");
+    let hosts_path = dir.join("hosts");
+    let host_list = hosts.join("\n");
+    let issued_for = std::fs::read_to_string(&hosts_path).ok();
+    if cert_path.is_file() && key_path.is_file() && issued_for.as_deref() == Some(host_list.as_str()) {
+        return Ok((cert_path, key_path));
+    }
+
+    let generated = rcgen::generate_simple_self_signed(hosts.to_vec())
+        .map_err(|e| io::Error::other(e.to_string()))?;
+
+    crate::runtime::write_private_file(&key_path, &generated.signing_key.serialize_pem())?;
+    std::fs::write(&cert_path, generated.cert.pem())?;
+    // Written last: a partially written pair is regenerated next time.
+    std::fs::write(&hosts_path, &host_list)?;
+
+    Ok((cert_path, key_path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_ensure_self_signed_cert_generates_and_loads() {
+        let temp = TempDir::new().unwrap();
+        let hosts = vec!["localhost".to_string(), "127.0.0.1".to_string()];
+
+        let (cert, key) = ensure_self_signed_cert(temp.path(), &hosts).unwrap();
+        assert!(cert.is_file());
+        assert!(key.is_file());
+        assert!(load_tls_config(&cert, &key).is_ok());
+    }
+
+    #[test]
+    fn test_ensure_self_signed_cert_reuses_existing() {
+        let temp = TempDir::new().unwrap();
+        let hosts = vec!["localhost".to_string()];
+
+        let (cert, _) = ensure_self_signed_cert(temp.path(), &hosts).unwrap();
+        let first = std::fs::read(&cert).unwrap();
+        ensure_self_signed_cert(temp.path(), &hosts).unwrap();
+        assert_eq!(first, std::fs::read(&cert).unwrap());
+    }
+
+    #[test]
+    fn test_ensure_self_signed_cert_regenerates_for_new_hosts() {
+        let temp = TempDir::new().unwrap();
+
+        let (cert, key) = ensure_self_signed_cert(temp.path(), &["localhost".to_string()]).unwrap();
+        let first = std::fs::read(&cert).unwrap();
+        ensure_self_signed_cert(temp.path(), &["localhost".to_string(), "mdv.lan".to_string()]).unwrap();
+        assert_ne!(first, std::fs::read(&cert).unwrap());
+        assert!(load_tls_config(&cert, &key).is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_ensure_self_signed_cert_private_key() {
+        let temp = TempDir::new().unwrap();
+        let (_, key) = ensure_self_signed_cert(&temp.path().join("tls"), &["localhost".to_string()]).unwrap();
+        assert_eq!(std::fs::metadata(&key).unwrap().permissions().mode() & 0o777, 0o600);
+    }
+
+    #[test]
+    fn test_load_tls_config_rejects_garbage() {
+        let temp = TempDir::new().unwrap();
+        let cert = temp.path().join("cert.pem");
+        let key = temp.path().join("key.pem");
+        std::fs::write(&cert, "not a certificate").unwrap();
+        std::fs::write(&key, "not a key").unwrap();
+
+        assert!(load_tls_config(&cert, &key).is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_bind_unix_socket_permissions() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("mdv.sock");
+
+        let _listener = bind_unix_socket(&path).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_bind_unix_socket_replaces_stale() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("mdv.sock");
+
+        drop(bind_unix_socket(&path).unwrap());
+        assert!(path.exists());
+        assert!(bind_unix_socket(&path).is_ok());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_bind_unix_socket_in_use() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("mdv.sock");
+
+        let _listener = bind_unix_socket(&path).unwrap();
+        let err = bind_unix_socket(&path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AddrInUse);
+    }
+}
@@ -0,0 +1,44 @@
+use tracing_subscriber::EnvFilter;
+
+/// Output format of server logs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum LogFormat {
+    /// Human-readable lines
+    #[default]
+    Text,
+    /// One JSON object per line, for log collectors
+    Json,
+}
+
+/// Default filter when neither `--log-level` nor `RUST_LOG` is set.
+const DEFAULT_FILTER: &str = "info";
+
+/// Installs the global tracing subscriber writing to stderr. `level`
+/// (from `--log-level`) takes precedence over `RUST_LOG`; both accept
+/// either a plain level or full filter directives such as
+/// `info,tower_http=debug`.
+pub fn init(level: Option<&str>, format: LogFormat) -> Result<(), String> {
+    let filter = match level {
+        Some(level) => EnvFilter::try_new(level).map_err(|e| format!("Invalid log level {:?}: {}", level, e))?,
+        None => EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_FILTER)),
+    };
+
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr);
+    let result = match format {
+        LogFormat::Text => builder.try_init(),
+        LogFormat::Json => builder.json().flatten_event(true).try_init(),
+    };
+    result.map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_init_rejects_invalid_filter() {
+        assert!(init(Some("info,[=bad"), LogFormat::Text).is_err());
+    }
+}
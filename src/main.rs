@@ -2,12 +2,13 @@ use askama::Template;
 use axum::{
     extract::{
         ws::{Message, WebSocket, WebSocketUpgrade},
-        Path, Query, State,
+        Path, Query, RawQuery, State,
     },
-    http::{header, StatusCode},
+    http::{header, HeaderMap, StatusCode},
+    middleware,
     response::{
         sse::{Event, KeepAlive, Sse},
-        Html, IntoResponse, Response,
+        Html, IntoResponse, Redirect, Response,
     },
     routing::{delete, get, post},
     Json, Router,
@@ -15,29 +16,298 @@ use axum::{
 use chrono::{DateTime, Local};
 use clap::Parser;
 use futures::{stream::Stream, SinkExt, StreamExt};
-use notify::{Config, PollWatcher, RecursiveMode, Watcher};
-use pulldown_cmark::{html, Options, Parser as MdParser};
+use notify::{
+    event::{ModifyKind, RenameMode},
+    EventKind, PollWatcher, RecursiveMode, Watcher,
+};
+use pulldown_cmark::{html, CowStr, Event as MdEvent, Parser as MdParser, Tag};
 use serde::{Deserialize, Serialize};
 use std::{
     collections::HashMap,
     convert::Infallible,
     fs,
+    future::{Future, IntoFuture},
     path::PathBuf,
-    sync::Arc,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
 };
 use tokio::sync::{broadcast, RwLock};
+use tokio_util::sync::CancellationToken;
+use tower_http::{
+    compression::{
+        predicate::{NotForContentType, Predicate, SizeAbove},
+        CompressionLayer,
+    },
+    trace::{DefaultOnResponse, TraceLayer},
+};
+use tracing::{debug, error, info, info_span, warn, Level};
+
+mod cli;
+mod client;
+mod config;
+mod daemon;
+mod dashboard;
+mod http_cache;
+mod listener;
+mod logging;
+mod metrics;
+mod recent;
+mod runtime;
+mod sanitize;
+mod security;
+mod static_file;
+
+use config::{FileFilter, NestedWorkspaces, WatcherMode};
+use http_cache::{RenderCache, Validators};
+use metrics::{GaugeGuard, METRICS};
+use recent::RecentFiles;
+use security::{OriginPolicy, RegisterPolicy};
 
 #[derive(Parser)]
 #[command(name = "mdv")]
 #[command(about = "Markdown Directory Viewer - A multi-workspace markdown preview server")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<cli::Command>,
+
+    /// Markdown file or directory to preview right away
+    path: Option<PathBuf>,
+
+    /// Open the preview in the default browser
+    #[arg(long, requires = "path")]
+    open: bool,
+
+    /// Exit once the last browser tab showing the preview is closed
+    #[arg(long, requires = "path")]
+    once: bool,
+
+    /// Run the server in the background, writing a pidfile and runtime file
+    #[arg(long)]
+    daemon: bool,
+
+    #[command(flatten)]
+    args: Args,
+}
+
+/// Server options. They are global so that client subcommands can locate
+/// (or spawn) the matching server, e.g. `mdv status --port 4000`.
+#[derive(clap::Args)]
 struct Args {
-    /// Port to listen on
-    #[arg(short, long, default_value = "3000")]
-    port: u16,
+    /// Port to listen on [default: 3000, or no TCP listener when --socket is given]
+    #[arg(short, long, global = true)]
+    port: Option<u16>,
 
-    /// Host to bind to
-    #[arg(long, default_value = "127.0.0.1")]
-    host: String,
+    /// Host to bind to [default: 127.0.0.1]
+    #[arg(long, global = true)]
+    host: Option<String>,
+
+    /// Extra origin allowed to call the server (repeatable), e.g. http://devbox.local:3000
+    #[arg(long = "allow-origin", value_name = "ORIGIN", global = true)]
+    allow_origins: Vec<String>,
+
+    /// Directory under which workspaces may be registered (repeatable, default: home)
+    #[arg(long = "allow-root", value_name = "PATH", global = true)]
+    allow_roots: Vec<PathBuf>,
+
+    /// Directory that may never be registered or served (repeatable, adds to ~/.ssh, ~/.gnupg, ~/.aws)
+    #[arg(long = "deny-root", value_name = "PATH", global = true)]
+    deny_roots: Vec<PathBuf>,
+
+    /// Serve over a Unix domain socket at PATH (created with 0600 permissions)
+    #[arg(long, value_name = "PATH", global = true)]
+    socket: Option<PathBuf>,
+
+    /// Serve HTTPS on the TCP listener (self-signed unless --tls-cert/--tls-key are given)
+    #[arg(long, global = true)]
+    tls: bool,
+
+    /// PEM certificate chain for HTTPS (implies --tls)
+    #[arg(long, value_name = "PATH", requires = "tls_key", global = true)]
+    tls_cert: Option<PathBuf>,
+
+    /// PEM private key for HTTPS (implies --tls)
+    #[arg(long, value_name = "PATH", requires = "tls_cert", global = true)]
+    tls_key: Option<PathBuf>,
+
+    /// Configuration file [default: ~/.config/mdv/config.toml]
+    #[arg(long, value_name = "PATH", global = true)]
+    config: Option<PathBuf>,
+
+    /// Log level or filter directives, e.g. debug or info,tower_http=debug [default: $RUST_LOG or info]
+    #[arg(long, value_name = "FILTER", global = true)]
+    log_level: Option<String>,
+
+    /// Log output format
+    #[arg(long, value_enum, default_value_t, global = true)]
+    log_format: logging::LogFormat,
+}
+
+impl Args {
+    fn host(&self) -> &str {
+        self.host.as_deref().unwrap_or(DEFAULT_HOST)
+    }
+
+    /// Merges the configuration file with the command line: flags take
+    /// precedence for host and port, list options are combined. Afterwards
+    /// `self` and `config` describe the same server.
+    fn apply_config(&mut self, config: &mut config::Config) {
+        match &self.host {
+            Some(host) => config.server.host = host.clone(),
+            None => self.host = Some(config.server.host.clone()),
+        }
+        self.port = self.port.or(config.server.port);
+        config.server.port = self.port;
+
+        fn union<T: Clone + PartialEq>(cli: &mut Vec<T>, file: &mut Vec<T>) {
+            for item in file.iter() {
+                if !cli.contains(item) {
+                    cli.push(item.clone());
+                }
+            }
+            *file = cli.clone();
+        }
+        union(&mut self.allow_origins, &mut config.security.allow_origins);
+        union(&mut self.allow_roots, &mut config.security.allow_roots);
+        union(&mut self.deny_roots, &mut config.security.deny_roots);
+    }
+
+    /// Port of the TCP listener; none when only a Unix socket was requested.
+    fn tcp_port(&self) -> Option<u16> {
+        match (&self.socket, self.port) {
+            (Some(_), None) => None,
+            (_, port) => Some(port.unwrap_or(DEFAULT_PORT)),
+        }
+    }
+
+    /// Name under which this server's runtime files are stored.
+    fn instance_name(&self) -> String {
+        runtime::instance_name(self.tcp_port(), self.socket.as_deref())
+    }
+
+    /// Reconstructs the command line flags for spawning an equivalent server.
+    fn to_cli_args(&self) -> Vec<std::ffi::OsString> {
+        let mut out: Vec<std::ffi::OsString> = Vec::new();
+        let mut push = |flag: &str, value: &std::ffi::OsStr| {
+            out.push(flag.into());
+            out.push(value.to_os_string());
+        };
+
+        if let Some(port) = self.port {
+            push("--port", port.to_string().as_ref());
+        }
+        push("--host", self.host().as_ref());
+        for origin in &self.allow_origins {
+            push("--allow-origin", origin.as_ref());
+        }
+        for root in &self.allow_roots {
+            push("--allow-root", root.as_os_str());
+        }
+        for root in &self.deny_roots {
+            push("--deny-root", root.as_os_str());
+        }
+        if let Some(socket) = &self.socket {
+            push("--socket", socket.as_os_str());
+        }
+        if let (Some(cert), Some(key)) = (&self.tls_cert, &self.tls_key) {
+            push("--tls-cert", cert.as_os_str());
+            push("--tls-key", key.as_os_str());
+        }
+        if let Some(config) = &self.config {
+            push("--config", config.as_os_str());
+        }
+        if let Some(level) = &self.log_level {
+            push("--log-level", level.as_ref());
+        }
+        if self.log_format == logging::LogFormat::Json {
+            push("--log-format", "json".as_ref());
+        }
+        if self.tls {
+            out.push("--tls".into());
+        }
+        out
+    }
+}
+
+const DEFAULT_HOST: &str = "127.0.0.1";
+const DEFAULT_PORT: u16 = 3000;
+
+/// How long `--once` waits after the last tab disconnects before exiting,
+/// so that reloads and navigation between documents do not end the session.
+const ONCE_GRACE_PERIOD: Duration = Duration::from_secs(3);
+
+/// How long open connections may take to finish after shutdown starts.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often watcher threads check whether they should stop.
+const WATCHER_STOP_CHECK: Duration = Duration::from_millis(200);
+
+/// Default number of files returned by `/api/recent`.
+const RECENT_LIMIT: usize = 20;
+
+/// Number of recently viewed and changed files shown above a listing.
+const DIRECTORY_RECENT: usize = 5;
+
+/// Quick preview requested on the command line (`mdv path/to/file.md`).
+struct Preview {
+    path: PathBuf,
+    open: bool,
+    once: bool,
+}
+
+type ServerFuture = Pin<Box<dyn Future<Output = std::io::Result<()>> + Send>>;
+
+/// Resolves the certificate and key to serve HTTPS with, if TLS is enabled.
+/// Without explicit files a self-signed pair is generated once under the
+/// user data directory and reused on later runs.
+fn tls_paths(args: &Args) -> Option<(PathBuf, PathBuf)> {
+    if let (Some(cert), Some(key)) = (&args.tls_cert, &args.tls_key) {
+        return Some((cert.clone(), key.clone()));
+    }
+    if !args.tls {
+        return None;
+    }
+
+    let dir = self_signed_cert_dir();
+    let mut hosts = vec!["localhost".to_string(), "127.0.0.1".to_string(), "::1".to_string()];
+    let host = args.host().to_string();
+    let unspecified = host
+        .parse::<std::net::IpAddr>()
+        .is_ok_and(|ip| ip.is_unspecified());
+    if !unspecified && !hosts.contains(&host) {
+        hosts.push(host);
+    }
+
+    match listener::ensure_self_signed_cert(&dir, &hosts) {
+        Ok(paths) => {
+            info!(cert = %paths.0.display(), "Using self-signed certificate");
+            Some(paths)
+        }
+        Err(e) => {
+            error!(dir = %dir.display(), error = %e, "Cannot create self-signed certificate");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Directory holding the generated self-signed certificate.
+fn self_signed_cert_dir() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("mdv")
+        .join("tls")
+}
+
+/// Certificate a client should trust to reach the server over HTTPS,
+/// or `None` when the server does not use TLS.
+fn tls_root(args: &Args) -> Option<PathBuf> {
+    if let Some(cert) = &args.tls_cert {
+        return Some(cert.clone());
+    }
+    args.tls.then(|| self_signed_cert_dir().join("cert.pem"))
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -50,32 +320,179 @@ enum WsCommand {
 
 struct Workspace {
     id: String,
+    /// User-chosen name usable in place of `id` in URLs.
+    alias: Option<String>,
+    /// Canonical path, resolved once at registration so that requests do
+    /// not have to canonicalize it again.
     root_dir: PathBuf,
     name: String,
-    #[allow(dead_code)]
-    watcher_handle: Option<std::thread::JoinHandle<()>>,
+    /// Trusted workspaces render raw HTML in markdown without sanitizing.
+    trusted: bool,
+    /// User configuration overlaid with the workspace's `.mdv.toml`.
+    config: Arc<config::Config>,
+    recent: Arc<RecentFiles>,
+    /// File count and recent changes shown on the dashboard.
+    scan: Arc<dashboard::ScanCache>,
+    watcher: Option<WatcherHandle>,
+}
+
+/// Background thread polling a workspace for changes.
+struct WatcherHandle {
+    stop: Arc<AtomicBool>,
+    /// Set once the watcher has started watching the workspace.
+    watching: Arc<AtomicBool>,
+    thread: std::thread::JoinHandle<()>,
+}
+
+impl WatcherHandle {
+    /// "starting", "watching" or "failed" (the thread only exits early
+    /// when the watcher could not be set up).
+    fn state(&self) -> &'static str {
+        if self.thread.is_finished() {
+            "failed"
+        } else if self.watching.load(Ordering::Relaxed) {
+            "watching"
+        } else {
+            "starting"
+        }
+    }
+
+    /// Signals the thread to stop and waits for it to exit. Blocks for up
+    /// to `WATCHER_STOP_CHECK`, so call it from a blocking context.
+    fn stop(self) {
+        self.stop.store(true, Ordering::Relaxed);
+        let _ = self.thread.join();
+    }
+}
+
+impl Workspace {
+    /// The alias if there is one, otherwise the id.
+    fn url_key(&self) -> &str {
+        self.alias.as_deref().unwrap_or(&self.id)
+    }
+
+    fn register_response(&self) -> RegisterResponse {
+        RegisterResponse {
+            id: self.id.clone(),
+            name: self.name.clone(),
+            alias: self.alias.clone(),
+            url: format!("/view/{}", self.url_key()),
+        }
+    }
 }
 
 struct AppStateInner {
     workspaces: HashMap<String, Workspace>,
 }
 
+impl AppStateInner {
+    /// Looks up a workspace by id or alias.
+    fn resolve(&self, key: &str) -> Option<&Workspace> {
+        self.workspaces
+            .get(key)
+            .or_else(|| self.workspaces.values().find(|w| w.alias.as_deref() == Some(key)))
+    }
+
+    fn id_for_root(&self, root_dir: &std::path::Path) -> Option<String> {
+        self.workspaces
+            .values()
+            .find(|w| w.root_dir == root_dir)
+            .map(|w| w.id.clone())
+    }
+
+    /// Picks an id for a new workspace at `root_dir`. Falls back to the
+    /// full hash when the short id is taken by another directory, and
+    /// returns `None` if that is taken too.
+    fn allocate_id(&self, root_dir: &std::path::Path) -> Option<String> {
+        [8, 16]
+            .into_iter()
+            .map(|digits| generate_workspace_id(root_dir, digits))
+            .find(|id| self.resolve(id).is_none())
+    }
+
+    /// Applies re-registration options to an existing workspace.
+    fn update(&mut self, id: &str, options: &WorkspaceOptions) -> Result<(), (StatusCode, &'static str)> {
+        if let Some(alias) = options.alias.as_deref().filter(|alias| !alias.is_empty()) {
+            if self.resolve(alias).is_some_and(|w| w.id != id) {
+                return Err((StatusCode::CONFLICT, "Alias is already in use"));
+            }
+        }
+        let Some(workspace) = self.workspaces.get_mut(id) else {
+            return Err((StatusCode::NOT_FOUND, "Workspace not found"));
+        };
+        if let Some(trusted) = options.trusted {
+            workspace.trusted = trusted;
+        }
+        if let Some(name) = &options.name {
+            workspace.name = name.clone();
+        }
+        if let Some(alias) = &options.alias {
+            workspace.alias = Some(alias.clone()).filter(|alias| !alias.is_empty());
+        }
+        Ok(())
+    }
+}
+
 #[derive(Clone)]
 struct AppState {
     inner: Arc<RwLock<AppStateInner>>,
     reload_tx: broadcast::Sender<String>,
     ws_tx: broadcast::Sender<WsCommand>,
+    origin_policy: Arc<OriginPolicy>,
+    register_policy: Arc<RegisterPolicy>,
+    /// Number of connected browser tabs (WebSocket clients).
+    ws_clients: Arc<tokio::sync::watch::Sender<usize>>,
+    /// Cancelled when the server begins shutting down.
+    shutdown: CancellationToken,
+    /// Secret required by privileged control APIs such as shutdown.
+    api_token: Arc<String>,
+    /// User-level configuration merged with command line flags.
+    config: Arc<config::Config>,
+    render_cache: Arc<RenderCache>,
 }
 
 #[derive(Deserialize)]
 struct RegisterRequest {
     path: String,
+    #[serde(flatten)]
+    options: WorkspaceOptions,
+}
+
+/// Settings given when registering a workspace. Fields left out keep
+/// their current value on re-registration.
+#[derive(Deserialize, Default)]
+struct WorkspaceOptions {
+    #[serde(default)]
+    trusted: Option<bool>,
+    /// Display name [default: directory name].
+    #[serde(default)]
+    name: Option<String>,
+    /// An empty alias removes the current one.
+    #[serde(default)]
+    alias: Option<String>,
+}
+
+impl WorkspaceOptions {
+    fn validate(&self) -> Result<(), (StatusCode, &'static str)> {
+        if self.name.as_deref().is_some_and(|name| name.trim().is_empty()) {
+            return Err((StatusCode::BAD_REQUEST, "Name must not be empty"));
+        }
+        if self.alias.as_deref().is_some_and(|alias| !alias.is_empty() && !is_valid_alias(alias)) {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "Invalid alias: use up to 64 letters, digits, '-', '_' or '.'",
+            ));
+        }
+        Ok(())
+    }
 }
 
 #[derive(Serialize)]
 struct RegisterResponse {
     id: String,
     name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    alias: Option<String>,
     url: String,
 }
 
@@ -87,6 +504,7 @@ struct ActiveQuery {
 #[derive(Serialize)]
 struct StatusResponse {
     status: String,
+    pid: u32,
     workspaces: Vec<WorkspaceInfo>,
 }
 
@@ -94,7 +512,226 @@ struct StatusResponse {
 struct WorkspaceInfo {
     id: String,
     name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    alias: Option<String>,
     path: String,
+    trusted: bool,
+}
+
+#[derive(Deserialize)]
+struct RecentQuery {
+    /// Workspace id or alias; all workspaces when absent.
+    workspace: Option<String>,
+    limit: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct RecentResponse {
+    viewed: Vec<RecentItem>,
+    changed: Vec<RecentItem>,
+}
+
+#[derive(Serialize)]
+struct RecentItem {
+    workspace_id: String,
+    workspace_name: String,
+    path: String,
+    url: String,
+    /// RFC 3339 timestamp.
+    time: String,
+}
+
+/// Query parameters of a directory listing.
+#[derive(Debug, Default)]
+struct ListingQuery {
+    sort: SortKey,
+    order: SortOrder,
+    /// Case-insensitive substring entry names must contain.
+    filter: String,
+    /// Also list files that are not markdown.
+    all: bool,
+    format: ListingFormat,
+    /// The URL has listing parameters, so the listing itself was asked for.
+    explicit: bool,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum SortKey {
+    #[default]
+    Name,
+    Size,
+    Modified,
+}
+
+impl SortKey {
+    fn as_str(self) -> &'static str {
+        match self {
+            SortKey::Name => "name",
+            SortKey::Size => "size",
+            SortKey::Modified => "modified",
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl SortOrder {
+    fn as_str(self) -> &'static str {
+        match self {
+            SortOrder::Asc => "asc",
+            SortOrder::Desc => "desc",
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ListingFormat {
+    #[default]
+    Html,
+    Json,
+}
+
+/// Parses one query parameter value into a `lowercase` serde enum.
+fn parse_param<T: serde::de::DeserializeOwned>(value: &str) -> Option<T> {
+    use serde::de::{value::StrDeserializer, IntoDeserializer};
+    let deserializer: StrDeserializer<serde::de::value::Error> = value.into_deserializer();
+    T::deserialize(deserializer).ok()
+}
+
+impl ListingQuery {
+    /// Parses the query string of a `/view` URL. The same URLs serve
+    /// markdown files, so unknown parameters and invalid values fall back
+    /// to the defaults instead of failing the request.
+    fn parse(query: Option<&str>) -> Self {
+        let mut listing = ListingQuery::default();
+        let params: Vec<(String, String)> = serde_urlencoded::from_str(query.unwrap_or("")).unwrap_or_default();
+        for (key, value) in params {
+            match key.as_str() {
+                "sort" => listing.sort = parse_param(&value).unwrap_or_default(),
+                "order" => listing.order = parse_param(&value).unwrap_or_default(),
+                "filter" => listing.filter = value,
+                "all" => listing.all = matches!(value.to_ascii_lowercase().as_str(), "true" | "1" | "on" | "yes"),
+                "format" => listing.format = parse_param(&value).unwrap_or_default(),
+                _ => continue,
+            }
+            listing.explicit = true;
+        }
+        listing
+    }
+
+    /// Query string for `sort`/`order`, keeping the filter and the "show
+    /// all" toggle.
+    fn with_sort(&self, sort: SortKey, order: SortOrder) -> String {
+        let mut params = vec![("sort", sort.as_str()), ("order", order.as_str())];
+        if !self.filter.is_empty() {
+            params.push(("filter", &self.filter));
+        }
+        if self.all {
+            params.push(("all", "true"));
+        }
+        format!("?{}", serde_urlencoded::to_string(params).unwrap_or_default())
+    }
+
+    /// Query string carried into subdirectories: everything but the filter.
+    fn carried(&self) -> String {
+        let mut params = Vec::new();
+        if self.sort != SortKey::Name || self.order != SortOrder::Asc {
+            params.push(("sort", self.sort.as_str()));
+            params.push(("order", self.order.as_str()));
+        }
+        if self.all {
+            params.push(("all", "true"));
+        }
+        if params.is_empty() {
+            return String::new();
+        }
+        format!("?{}", serde_urlencoded::to_string(params).unwrap_or_default())
+    }
+
+    /// Filters and sorts `entries` as requested.
+    fn apply(&self, entries: &mut Vec<FileEntry>) {
+        let filter = self.filter.trim().to_lowercase();
+        if !filter.is_empty() {
+            entries.retain(|entry| entry.name.to_lowercase().contains(&filter));
+        }
+        sort_entries(entries, self.sort, self.order);
+    }
+}
+
+#[derive(Serialize)]
+struct ListingResponse {
+    path: String,
+    entries: Vec<ListingEntry>,
+}
+
+#[derive(Serialize)]
+struct ListingEntry {
+    name: String,
+    /// Relative to the workspace root, as accepted by `TreeQuery::path`.
+    path: String,
+    url: String,
+    is_dir: bool,
+    is_markdown: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size: Option<u64>,
+    /// RFC 3339 timestamp.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    modified: Option<String>,
+}
+
+impl ListingEntry {
+    /// `entry` as listed in `dir`, a directory relative to the workspace root.
+    fn new(dir: &str, entry: FileEntry) -> Self {
+        ListingEntry {
+            path: if dir.is_empty() { entry.name.clone() } else { format!("{}/{}", dir, entry.name) },
+            name: entry.name,
+            url: entry.path,
+            is_dir: entry.is_dir,
+            is_markdown: !entry.is_dir && !entry.is_raw,
+            size: (!entry.is_dir).then_some(entry.size_bytes),
+            modified: entry.modified_time.map(|time| DateTime::<chrono::Utc>::from(time).to_rfc3339()),
+        }
+    }
+}
+
+/// A sortable column header of a directory listing.
+struct SortHeader {
+    url: String,
+    /// Shown next to the active column.
+    arrow: &'static str,
+}
+
+impl SortHeader {
+    fn new(query: &ListingQuery, key: SortKey) -> Self {
+        let active = query.sort == key;
+        // Names read best A to Z, sizes and times largest / newest first.
+        let order = match (active, query.order, key) {
+            (true, SortOrder::Asc, _) => SortOrder::Desc,
+            (true, SortOrder::Desc, _) => SortOrder::Asc,
+            (false, _, SortKey::Name) => SortOrder::Asc,
+            (false, _, _) => SortOrder::Desc,
+        };
+        let arrow = match (active, query.order) {
+            (false, _) => "",
+            (true, SortOrder::Asc) => "\u{2191}",
+            (true, SortOrder::Desc) => "\u{2193}",
+        };
+        SortHeader { url: query.with_sort(key, order), arrow }
+    }
+}
+
+#[derive(Deserialize)]
+struct TreeQuery {
+    /// Directory relative to the workspace root; the root when absent.
+    path: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -114,8 +751,31 @@ struct FileEntry {
     name: String,
     path: String,
     is_dir: bool,
+    /// Not markdown; linked to its raw content.
+    is_raw: bool,
     size: String,
     modified: String,
+    size_bytes: u64,
+    modified_time: Option<std::time::SystemTime>,
+}
+
+struct IndexDocument {
+    name: String,
+    url: String,
+    content: String,
+}
+
+/// A recently viewed or changed file shown above a directory listing.
+struct RecentLink {
+    /// Path relative to the listed directory.
+    name: String,
+    url: String,
+    time: String,
+}
+
+struct RecentSection {
+    title: &'static str,
+    links: Vec<RecentLink>,
 }
 
 #[derive(Template)]
@@ -123,10 +783,23 @@ struct FileEntry {
 struct DirectoryTemplate {
     breadcrumbs: Vec<BreadcrumbItem>,
     entries: Vec<FileEntry>,
+    /// README or index document of the directory, rendered below the entries.
+    index: Option<IndexDocument>,
+    /// Non-empty recently viewed / changed lists.
+    recent: Vec<RecentSection>,
     has_parent: bool,
     parent_path: String,
+    sort_name: SortHeader,
+    sort_size: SortHeader,
+    sort_modified: SortHeader,
+    sort: &'static str,
+    order: &'static str,
+    filter: String,
+    show_all: bool,
     workspace_id: String,
     workspace_name: String,
+    theme: &'static str,
+    csp_nonce: String,
 }
 
 #[derive(Template)]
@@ -137,25 +810,47 @@ struct MarkdownTemplate {
     filename: String,
     file_size: String,
     raw_path: String,
+    /// Endpoint the file tree sidebar loads directories from.
+    tree_api: String,
+    /// Highlighted in the file tree.
+    file_path: String,
     workspace_id: String,
     workspace_name: String,
+    theme: &'static str,
+    csp_nonce: String,
 }
 
-fn generate_workspace_id(path: &PathBuf) -> String {
-    let name = path
+/// 64-bit FNV-1a. Unlike `DefaultHasher` its output is fixed, so ids and
+/// the bookmarks using them survive restarts and Rust upgrades.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// `<directory name>-<hash>`, with `digits` (at most 16) hex digits of the
+/// path's hash. Characters that need escaping in URLs become `-`.
+fn generate_workspace_id(path: &std::path::Path, digits: usize) -> String {
+    let name: String = path
         .file_name()
-        .and_then(|n| n.to_str())
-        .unwrap_or("workspace");
+        .map(|n| n.to_string_lossy())
+        .unwrap_or("workspace".into())
+        .chars()
+        .map(|c| if is_alias_char(c) { c } else { '-' })
+        .collect();
+    let hash = fnv1a(path.as_os_str().as_encoded_bytes()) >> (64 - 4 * digits);
+    format!("{}-{:0width$x}", name, hash, width = digits)
+}
 
-    let hash = {
-        use std::collections::hash_map::DefaultHasher;
-        use std::hash::{Hash, Hasher};
-        let mut hasher = DefaultHasher::new();
-        path.hash(&mut hasher);
-        hasher.finish()
-    };
+fn is_alias_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '-' | '_' | '.')
+}
 
-    format!("{}-{:x}", name, hash & 0xFFFF)
+fn is_valid_alias(alias: &str) -> bool {
+    !alias.is_empty()
+        && alias.chars().count() <= 64
+        && !alias.starts_with('.')
+        && alias.chars().all(is_alias_char)
 }
 
 fn generate_breadcrumbs(workspace_id: &str, workspace_name: &str, path: &str) -> Vec<BreadcrumbItem> {
@@ -212,32 +907,84 @@ fn format_datetime(time: std::time::SystemTime) -> String {
     datetime.format("%Y-%m-%d %H:%M").to_string()
 }
 
-fn render_markdown(content: &str) -> String {
-    let mut options = Options::empty();
-    options.insert(Options::ENABLE_TABLES);
-    options.insert(Options::ENABLE_FOOTNOTES);
-    options.insert(Options::ENABLE_STRIKETHROUGH);
-    options.insert(Options::ENABLE_TASKLISTS);
-
-    let parser = MdParser::new_ext(content, options);
+/// Renders `content` to HTML. Relative link and image URLs are resolved
+/// against `base` (ending in `/`) when given, for documents shown away
+/// from their own URL.
+fn render_markdown(content: &str, config: &config::MarkdownConfig, base: Option<&str>) -> String {
+    let parser = MdParser::new_ext(content, config.options()).map(|event| match (base, event) {
+        (Some(base), MdEvent::Start(Tag::Link { link_type, dest_url, title, id })) => MdEvent::Start(Tag::Link {
+            link_type,
+            dest_url: rebase_url(base, dest_url),
+            title,
+            id,
+        }),
+        (Some(base), MdEvent::Start(Tag::Image { link_type, dest_url, title, id })) => MdEvent::Start(Tag::Image {
+            link_type,
+            dest_url: rebase_url(base, dest_url),
+            title,
+            id,
+        }),
+        (_, event) => event,
+    });
     let mut html_output = String::new();
     html::push_html(&mut html_output, parser);
     html_output
 }
 
-fn contains_markdown(path: &PathBuf) -> bool {
+/// Prefixes relative `url` with `base`; absolute URLs, fragments and
+/// queries are returned unchanged.
+fn rebase_url<'a>(base: &str, url: CowStr<'a>) -> CowStr<'a> {
+    let scheme = url.split(['/', '?', '#']).next().unwrap_or("");
+    if url.is_empty() || url.starts_with(['/', '#', '?']) || scheme.contains(':') {
+        return url;
+    }
+    format!("{}{}", base, url).into()
+}
+
+/// Percent-encodes a URL path, keeping `/`.
+fn encode_path(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-._~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+/// The first of `names` present in `dir` as a markdown file, compared
+/// case-insensitively. Blocking.
+fn find_index_file(dir: &std::path::Path, names: &[String], filter: &FileFilter) -> Option<PathBuf> {
+    let files: Vec<(String, PathBuf)> = fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_ok_and(|t| t.is_file()))
+        .map(|entry| (entry.file_name().to_string_lossy().to_string(), entry.path()))
+        .filter(|(name, path)| !name.starts_with('.') && !filter.is_ignored(name) && filter.is_markdown(path))
+        .collect();
+    names.iter().find_map(|wanted| {
+        files
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(wanted))
+            .map(|(_, path)| path.clone())
+    })
+}
+
+fn contains_markdown(path: &PathBuf, filter: &FileFilter) -> bool {
     if path.is_file() {
-        return path.extension().and_then(|e| e.to_str()) == Some("md");
+        return filter.is_markdown(path);
     }
 
     if let Ok(entries) = fs::read_dir(path) {
         for entry in entries.filter_map(|e| e.ok()) {
             let entry_path = entry.path();
             let name = entry.file_name().to_string_lossy().to_string();
-            if name.starts_with('.') {
+            if name.starts_with('.') || filter.is_ignored(&name) {
                 continue;
             }
-            if contains_markdown(&entry_path) {
+            if contains_markdown(&entry_path, filter) {
                 return true;
             }
         }
@@ -245,44 +992,59 @@ fn contains_markdown(path: &PathBuf) -> bool {
     false
 }
 
-fn validate_path(root: &PathBuf, requested_path: &str) -> Option<PathBuf> {
-    let cleaned_path = requested_path.trim_start_matches('/');
-    let full_path = root.join(cleaned_path);
+/// Whether any component of `path` below `root` is hidden or ignored.
+fn is_ignored_path(filter: &FileFilter, root: &std::path::Path, path: &std::path::Path) -> bool {
+    path.strip_prefix(root).unwrap_or(path).components().any(|c| {
+        let name = c.as_os_str().to_string_lossy();
+        name.starts_with('.') || filter.is_ignored(&name)
+    })
+}
 
-    match full_path.canonicalize() {
-        Ok(canonical) => {
-            let root_canonical = root.canonicalize().ok()?;
-            if canonical.starts_with(&root_canonical) {
-                Some(canonical)
-            } else {
-                None
-            }
-        }
-        Err(_) => None,
-    }
+/// Resolves `requested_path` below the canonical `root`, rejecting paths
+/// that do not exist or escape it (through `..` or symlinks).
+async fn validate_path(root: &std::path::Path, requested_path: &str) -> Option<PathBuf> {
+    let cleaned_path = requested_path.trim_start_matches('/');
+    let canonical = tokio::fs::canonicalize(root.join(cleaned_path)).await.ok()?;
+    canonical.starts_with(root).then_some(canonical)
 }
 
 fn json_error(status: StatusCode, message: &str) -> Response {
     (status, Json(serde_json::json!({"error": message}))).into_response()
 }
 
-/// Finds the workspace containing the given absolute file path.
+/// Renders an HTML page template with a CSP matching its script nonce.
+fn render_page<T: Template>(template: &T, csp_nonce: &str) -> Response {
+    match template.render() {
+        Ok(html) => (
+            [(header::CONTENT_SECURITY_POLICY, security::page_csp(csp_nonce))],
+            Html(html),
+        )
+            .into_response(),
+        Err(e) => {
+            error!(error = %e, "Template rendering failed");
+            (StatusCode::INTERNAL_SERVER_ERROR, Html("Template error")).into_response()
+        }
+    }
+}
+
+/// Finds the workspace containing the given canonical file path, picking
+/// the innermost or outermost one when registered roots are nested.
 /// Returns the workspace ID and relative path within the workspace.
 fn find_workspace_for_path<'a>(
     workspaces: &'a HashMap<String, Workspace>,
     abs_path: &std::path::Path,
+    nested: NestedWorkspaces,
 ) -> Option<(&'a str, String)> {
-    for (id, workspace) in workspaces {
-        if let Ok(workspace_canonical) = workspace.root_dir.canonicalize() {
-            if abs_path.starts_with(&workspace_canonical) {
-                let relative = abs_path
-                    .strip_prefix(&workspace_canonical)
-                    .unwrap_or(abs_path);
-                return Some((id.as_str(), relative.to_string_lossy().to_string()));
-            }
-        }
-    }
-    None
+    let candidates = workspaces.iter().filter_map(|(id, workspace)| {
+        let relative = abs_path.strip_prefix(&workspace.root_dir).ok()?;
+        // Distinct roots that both contain the path differ in depth.
+        Some((workspace.root_dir.components().count(), id, relative))
+    });
+    let (_, id, relative) = match nested {
+        NestedWorkspaces::Innermost => candidates.max_by_key(|(depth, _, _)| *depth),
+        NestedWorkspaces::Outermost => candidates.min_by_key(|(depth, _, _)| *depth),
+    }?;
+    Some((id.as_str(), relative.to_string_lossy().to_string()))
 }
 
 // API: Register workspace
@@ -290,71 +1052,204 @@ async fn api_register(
     State(state): State<AppState>,
     Json(req): Json<RegisterRequest>,
 ) -> Response {
-    let path = PathBuf::from(&req.path);
-    let Ok(canonical_path) = path.canonicalize() else {
-        return json_error(StatusCode::BAD_REQUEST, "Invalid path");
+    match register_workspace(&state, std::path::Path::new(&req.path), &req.options).await {
+        Ok(response) => Json(response).into_response(),
+        Err((status, message)) => json_error(status, message),
+    }
+}
+
+/// Registers `path` as a workspace (or applies `options` to it if it is
+/// already registered) and starts watching it for changes.
+async fn register_workspace(
+    state: &AppState,
+    path: &std::path::Path,
+    options: &WorkspaceOptions,
+) -> Result<RegisterResponse, (StatusCode, &'static str)> {
+    options.validate()?;
+
+    let Ok(canonical_path) = tokio::fs::canonicalize(path).await else {
+        return Err((StatusCode::BAD_REQUEST, "Invalid path"));
     };
 
-    if !canonical_path.is_dir() {
-        return json_error(StatusCode::BAD_REQUEST, "Path is not a directory");
+    if !tokio::fs::metadata(&canonical_path).await.is_ok_and(|m| m.is_dir()) {
+        return Err((StatusCode::BAD_REQUEST, "Path is not a directory"));
     }
 
-    let workspace_id = generate_workspace_id(&canonical_path);
-    let workspace_name = canonical_path
-        .file_name()
-        .and_then(|n| n.to_str())
-        .unwrap_or("workspace")
-        .to_string();
+    if let Err(message) = state.register_policy.check(&canonical_path) {
+        return Err((StatusCode::FORBIDDEN, message));
+    }
+
+    {
+        let mut inner = state.inner.write().await;
+        if let Some(id) = inner.id_for_root(&canonical_path) {
+            inner.update(&id, options)?;
+            return Ok(inner.workspaces[&id].register_response());
+        }
+    }
+
+    // Reads the workspace's `.mdv.toml`.
+    let config = {
+        let user_config = state.config.clone();
+        let root = canonical_path.clone();
+        tokio::task::spawn_blocking(move || {
+            user_config.for_workspace(&root).unwrap_or_else(|message| {
+                warn!(workspace = %root.display(), "{}; using defaults", message);
+                user_config.as_ref().clone()
+            })
+        })
+        .await
+        .unwrap_or_else(|_| state.config.as_ref().clone())
+    };
 
     let mut inner = state.inner.write().await;
 
-    if !inner.workspaces.contains_key(&workspace_id) {
-        let reload_tx = state.reload_tx.clone();
-        let watch_id = workspace_id.clone();
-        let watch_dir = canonical_path.clone();
+    // Registered concurrently while the configuration was being read.
+    if let Some(id) = inner.id_for_root(&canonical_path) {
+        inner.update(&id, options)?;
+        return Ok(inner.workspaces[&id].register_response());
+    }
+
+    if options
+        .alias
+        .as_deref()
+        .is_some_and(|alias| !alias.is_empty() && inner.resolve(alias).is_some())
+    {
+        return Err((StatusCode::CONFLICT, "Alias is already in use"));
+    }
+    let Some(workspace_id) = inner.allocate_id(&canonical_path) else {
+        return Err((StatusCode::CONFLICT, "Workspace id is already in use"));
+    };
+    let workspace_name = options.name.clone().unwrap_or_else(|| {
+        canonical_path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| "workspace".to_string())
+    });
+
+    let config = Arc::new(config);
+    let recent = Arc::new(RecentFiles::default());
+    let watcher = spawn_watcher(
+        workspace_id.clone(),
+        canonical_path.clone(),
+        config.clone(),
+        state.reload_tx.clone(),
+        state.render_cache.clone(),
+        recent.clone(),
+    );
 
-        let watcher_handle = std::thread::spawn(move || {
-            let (tx, rx) = std::sync::mpsc::channel();
-            let config = Config::default().with_poll_interval(std::time::Duration::from_millis(500));
-            let Ok(mut watcher) = PollWatcher::new(tx, config) else { return };
-            if watcher.watch(&watch_dir, RecursiveMode::Recursive).is_err() {
+    let workspace = Workspace {
+        id: workspace_id.clone(),
+        alias: options.alias.clone().filter(|alias| !alias.is_empty()),
+        root_dir: canonical_path.clone(),
+        name: workspace_name,
+        trusted: options.trusted.unwrap_or(false),
+        config,
+        recent,
+        scan: Arc::default(),
+        watcher: Some(watcher),
+    };
+    let response = workspace.register_response();
+    inner.workspaces.insert(workspace_id.clone(), workspace);
+    METRICS.workspaces.set(inner.workspaces.len() as i64);
+    info!(workspace = %workspace_id, path = %canonical_path.display(), "Workspace registered");
+
+    Ok(response)
+}
+
+/// Starts a thread watching `watch_dir` and broadcasting `watch_id` on
+/// `reload_tx` whenever a markdown file that is not ignored changes.
+/// Changed files are also recorded in `recent`.
+fn spawn_watcher(
+    watch_id: String,
+    watch_dir: PathBuf,
+    config: Arc<config::Config>,
+    reload_tx: broadcast::Sender<String>,
+    render_cache: Arc<RenderCache>,
+    recent: Arc<RecentFiles>,
+) -> WatcherHandle {
+    let stop = Arc::new(AtomicBool::new(false));
+    let thread_stop = stop.clone();
+    let watching = Arc::new(AtomicBool::new(false));
+    let thread_watching = watching.clone();
+
+    let thread = std::thread::spawn(move || {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let watcher: notify::Result<Box<dyn Watcher>> = match config.watcher.mode {
+            WatcherMode::Poll => {
+                let interval = Duration::from_millis(config.watcher.poll_interval_ms);
+                let notify_config = notify::Config::default().with_poll_interval(interval);
+                PollWatcher::new(tx, notify_config).map(|w| Box::new(w) as Box<dyn Watcher>)
+            }
+            WatcherMode::Native => notify::recommended_watcher(tx).map(|w| Box::new(w) as Box<dyn Watcher>),
+        };
+        let mut watcher = match watcher {
+            Ok(watcher) => watcher,
+            Err(e) => {
+                error!(workspace = %watch_id, error = %e, "Cannot create file watcher");
                 return;
             }
-
-            loop {
-                match rx.recv() {
-                    Ok(Ok(event)) => {
-                        let is_md = event.paths.iter().any(|p| {
-                            p.extension().and_then(|e| e.to_str()) == Some("md")
-                        });
-                        if is_md {
-                            let _ = reload_tx.send(watch_id.clone());
+        };
+        if let Err(e) = watcher.watch(&watch_dir, RecursiveMode::Recursive) {
+            error!(workspace = %watch_id, dir = %watch_dir.display(), error = %e, "Cannot watch workspace");
+            return;
+        }
+        debug!(workspace = %watch_id, mode = ?config.watcher.mode, "Watching workspace");
+        thread_watching.store(true, Ordering::Relaxed);
+        let _running = GaugeGuard::new(&METRICS.watchers);
+        let filter = config.files.filter();
+
+        while !thread_stop.load(Ordering::Relaxed) {
+            match rx.recv_timeout(WATCHER_STOP_CHECK) {
+                Ok(Ok(event)) => {
+                    for path in &event.paths {
+                        render_cache.invalidate(path);
+                    }
+                    let markdown: Vec<(usize, &PathBuf)> = event
+                        .paths
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, p)| filter.is_markdown(p) && !is_ignored_path(&filter, &watch_dir, p))
+                        .collect();
+                    for &(index, full_path) in &markdown {
+                        let Some(path) = recent::relative_path(&watch_dir, full_path) else {
+                            continue;
+                        };
+                        match recent_change(&event.kind, index) {
+                            // Renames reported without a side (e.g. on macOS) tell
+                            // old and new path apart only by which one exists.
+                            RecentChange::Changed if full_path.exists() => recent.record_changed(path),
+                            RecentChange::Changed | RecentChange::Gone => recent.forget(&path),
+                            RecentChange::None => {}
                         }
                     }
-                    Ok(Err(_)) => {}
-                    Err(_) => break,
+                    if !markdown.is_empty() {
+                        debug!(workspace = %watch_id, paths = ?event.paths, "Markdown changed");
+                        METRICS.reload_events.inc();
+                        let _ = reload_tx.send(watch_id.clone());
+                    }
                 }
+                Ok(Err(e)) => warn!(workspace = %watch_id, error = %e, "Watcher error"),
+                Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {}
+                Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => break,
             }
-        });
-
-        inner.workspaces.insert(
-            workspace_id.clone(),
-            Workspace {
-                id: workspace_id.clone(),
-                root_dir: canonical_path.clone(),
-                name: workspace_name.clone(),
-                watcher_handle: Some(watcher_handle),
-            },
-        );
-    }
+        }
+    });
 
-    let response = RegisterResponse {
-        id: workspace_id.clone(),
-        name: workspace_name,
-        url: format!("/view/{}", workspace_id),
-    };
+    WatcherHandle { stop, watching, thread }
+}
 
-    Json(response).into_response()
+/// Stops and joins the watchers of the given workspaces.
+async fn stop_watchers(workspaces: Vec<Workspace>) {
+    let watchers: Vec<WatcherHandle> = workspaces.into_iter().filter_map(|w| w.watcher).collect();
+    if watchers.is_empty() {
+        return;
+    }
+    let _ = tokio::task::spawn_blocking(move || {
+        for watcher in watchers {
+            watcher.stop();
+        }
+    })
+    .await;
 }
 
 // API: Remove workspace
@@ -362,35 +1257,78 @@ async fn api_unregister(
     State(state): State<AppState>,
     Path(workspace_id): Path<String>,
 ) -> Response {
-    let mut inner = state.inner.write().await;
+    let removed = {
+        let mut inner = state.inner.write().await;
+        let id = inner.resolve(&workspace_id).map(|w| w.id.clone());
+        let removed = id.and_then(|id| inner.workspaces.remove(&id));
+        METRICS.workspaces.set(inner.workspaces.len() as i64);
+        removed
+    };
 
-    if let Some(workspace) = inner.workspaces.remove(&workspace_id) {
-        drop(workspace.watcher_handle);
-        Json(serde_json::json!({"status": "ok", "id": workspace_id})).into_response()
+    if let Some(workspace) = removed {
+        let id = workspace.id.clone();
+        state.render_cache.invalidate_dir(&workspace.root_dir);
+        stop_watchers(vec![workspace]).await;
+        info!(workspace = %id, "Workspace removed");
+        Json(serde_json::json!({"status": "ok", "id": id})).into_response()
     } else {
         json_error(StatusCode::NOT_FOUND, "Workspace not found")
     }
 }
 
+// API: Rename a workspace, change its alias or trust setting
+async fn api_update(
+    State(state): State<AppState>,
+    Path(workspace_id): Path<String>,
+    Json(options): Json<WorkspaceOptions>,
+) -> Response {
+    if let Err((status, message)) = options.validate() {
+        return json_error(status, message);
+    }
+    let mut inner = state.inner.write().await;
+    let Some(id) = inner.resolve(&workspace_id).map(|w| w.id.clone()) else {
+        return json_error(StatusCode::NOT_FOUND, "Workspace not found");
+    };
+    match inner.update(&id, &options) {
+        Ok(()) => Json(inner.workspaces[&id].register_response()).into_response(),
+        Err((status, message)) => json_error(status, message),
+    }
+}
+
+// API: Shut down the server (requires the bearer token from the runtime dir)
+async fn api_shutdown(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    if !security::has_bearer_token(&headers, &state.api_token) {
+        return json_error(StatusCode::UNAUTHORIZED, "Missing or invalid token");
+    }
+    info!("Shutdown requested through the API");
+    state.shutdown.cancel();
+    Json(serde_json::json!({"status": "ok"})).into_response()
+}
+
 // API: Get active file URL and notify browser
 async fn api_active(
     State(state): State<AppState>,
     Query(query): Query<ActiveQuery>,
 ) -> Response {
-    let abs_path = PathBuf::from(&query.path);
-    let Ok(canonical_path) = abs_path.canonicalize() else {
+    let Ok(canonical_path) = tokio::fs::canonicalize(&query.path).await else {
         return json_error(StatusCode::BAD_REQUEST, "Invalid path");
     };
 
     let inner = state.inner.read().await;
 
     let Some((workspace_id, relative_path)) =
-        find_workspace_for_path(&inner.workspaces, &canonical_path)
+        find_workspace_for_path(&inner.workspaces, &canonical_path, state.config.server.nested_workspaces)
     else {
         return json_error(StatusCode::NOT_FOUND, "File not in any registered workspace");
     };
 
-    let url = format!("/view/{}/{}", workspace_id, relative_path);
+    let workspace = &inner.workspaces[workspace_id];
+    let url = encode_path(&format!("/view/{}/{}", workspace.url_key(), relative_path));
+    if workspace.config.files.filter().is_markdown(&canonical_path) {
+        if let Some(relative) = recent::relative_path(&workspace.root_dir, &canonical_path) {
+            workspace.recent.record_viewed(relative);
+        }
+    }
 
     let _ = state.ws_tx.send(WsCommand::Focus {
         workspace_id: workspace_id.to_string(),
@@ -405,6 +1343,76 @@ async fn api_active(
     .into_response()
 }
 
+// API: Recently viewed and changed files, across workspaces or for one
+async fn api_recent(State(state): State<AppState>, Query(query): Query<RecentQuery>) -> Response {
+    let inner = state.inner.read().await;
+    let workspaces: Vec<&Workspace> = match &query.workspace {
+        Some(key) => match inner.resolve(key) {
+            Some(workspace) => vec![workspace],
+            None => return json_error(StatusCode::NOT_FOUND, "Workspace not found"),
+        },
+        None => inner.workspaces.values().collect(),
+    };
+    let limit = query.limit.unwrap_or(RECENT_LIMIT);
+
+    let items = |lists: Vec<(&Workspace, Vec<recent::RecentEntry>)>| -> Vec<RecentItem> {
+        recent::merge(lists, limit)
+            .into_iter()
+            .map(|(workspace, entry)| RecentItem {
+                workspace_id: workspace.id.clone(),
+                workspace_name: workspace.name.clone(),
+                url: format!("/view/{}/{}", workspace.url_key(), entry.path),
+                path: entry.path,
+                time: DateTime::<chrono::Utc>::from(entry.time).to_rfc3339(),
+            })
+            .collect()
+    };
+    let viewed = items(workspaces.iter().map(|w| (*w, w.recent.viewed())).collect());
+    let changed = items(workspaces.iter().map(|w| (*w, w.recent.changed())).collect());
+
+    Json(RecentResponse { viewed, changed }).into_response()
+}
+
+// API: One directory level of the file tree, for the sidebar
+async fn api_tree(
+    State(state): State<AppState>,
+    Path(workspace_id): Path<String>,
+    Query(query): Query<TreeQuery>,
+) -> Response {
+    let Some((url_key, root_dir, config)) = state
+        .inner
+        .read()
+        .await
+        .resolve(&workspace_id)
+        .map(|w| (w.url_key().to_string(), w.root_dir.clone(), w.config.clone()))
+    else {
+        return json_error(StatusCode::NOT_FOUND, "Workspace not found");
+    };
+
+    let Some(full_path) = validate_path(&root_dir, query.path.as_deref().unwrap_or("")).await else {
+        return json_error(StatusCode::NOT_FOUND, "Directory not found");
+    };
+    if state.register_policy.is_denied(&full_path) {
+        return json_error(StatusCode::FORBIDDEN, "Forbidden");
+    }
+    if is_ignored_path(&config.files.filter(), &root_dir, &full_path) {
+        return json_error(StatusCode::NOT_FOUND, "Directory not found");
+    }
+    let dir = recent::relative_path(&root_dir, &full_path).unwrap_or_default();
+
+    let base_url = format!("/view/{}", url_key);
+    let listing = {
+        let dir = dir.clone();
+        tokio::task::spawn_blocking(move || list_directory(&full_path, &base_url, &dir, &config, None)).await
+    };
+    let Ok(Ok(entries)) = listing else {
+        return json_error(StatusCode::NOT_FOUND, "Directory not found");
+    };
+
+    let entries = entries.into_iter().map(|entry| ListingEntry::new(&dir, entry)).collect();
+    Json(ListingResponse { path: dir, entries }).into_response()
+}
+
 // API: Status check
 async fn api_status(State(state): State<AppState>) -> Json<StatusResponse> {
     let inner = state.inner.read().await;
@@ -414,12 +1422,15 @@ async fn api_status(State(state): State<AppState>) -> Json<StatusResponse> {
         .map(|w| WorkspaceInfo {
             id: w.id.clone(),
             name: w.name.clone(),
+            alias: w.alias.clone(),
             path: w.root_dir.to_string_lossy().to_string(),
+            trusted: w.trusted,
         })
         .collect();
 
     Json(StatusResponse {
         status: "ok".to_string(),
+        pid: std::process::id(),
         workspaces,
     })
 }
@@ -437,76 +1448,155 @@ async fn api_scroll(
 async fn handle_view_root(
     State(state): State<AppState>,
     Path(workspace_id): Path<String>,
+    RawQuery(query): RawQuery,
+    headers: HeaderMap,
 ) -> Response {
-    handle_view_path_internal(&state, &workspace_id, "").await
+    handle_view_path_internal(&state, &workspace_id, "", query.as_deref(), &headers).await
 }
 
 // View workspace path
 async fn handle_view_path(
     State(state): State<AppState>,
     Path((workspace_id, path)): Path<(String, String)>,
+    RawQuery(query): RawQuery,
+    headers: HeaderMap,
 ) -> Response {
-    handle_view_path_internal(&state, &workspace_id, &path).await
+    handle_view_path_internal(&state, &workspace_id, &path, query.as_deref(), &headers).await
 }
 
-async fn handle_view_path_internal(state: &AppState, workspace_id: &str, path: &str) -> Response {
-    let inner = state.inner.read().await;
-    let Some(workspace) = inner.workspaces.get(workspace_id) else {
-        return (StatusCode::NOT_FOUND, Html("Workspace not found")).into_response();
+/// `url_key` is the workspace id or alias the page was requested with;
+/// links on the page keep using it. `query` only applies to directories.
+async fn handle_view_path_internal(
+    state: &AppState,
+    url_key: &str,
+    path: &str,
+    query: Option<&str>,
+    headers: &HeaderMap,
+) -> Response {
+    let (workspace_id, root_dir, workspace_name, trusted, config, recent) = {
+        let inner = state.inner.read().await;
+        let Some(workspace) = inner.resolve(url_key) else {
+            return (StatusCode::NOT_FOUND, Html("Workspace not found")).into_response();
+        };
+        (
+            workspace.id.clone(),
+            workspace.root_dir.clone(),
+            workspace.name.clone(),
+            workspace.trusted,
+            workspace.config.clone(),
+            workspace.recent.clone(),
+        )
     };
 
-    let Some(full_path) = validate_path(&workspace.root_dir, path) else {
+    let Some(full_path) = validate_path(&root_dir, path).await else {
+        return (StatusCode::NOT_FOUND, Html("Not Found")).into_response();
+    };
+    if state.register_policy.is_denied(&full_path) {
+        return (StatusCode::FORBIDDEN, Html("Forbidden")).into_response();
+    }
+    let Ok(metadata) = tokio::fs::metadata(&full_path).await else {
         return (StatusCode::NOT_FOUND, Html("Not Found")).into_response();
     };
 
-    let workspace_name = workspace.name.clone();
-    drop(inner);
-
-    if full_path.is_dir() {
-        render_directory(workspace_id, &workspace_name, &full_path, path).await
-    } else if full_path.is_file() {
-        let extension = full_path.extension().and_then(|e| e.to_str()).unwrap_or("");
-        if extension == "md" {
-            render_markdown_file(workspace_id, &workspace_name, &full_path, path).await
+    if metadata.is_dir() {
+        let dir = recent::relative_path(&root_dir, &full_path).unwrap_or_default();
+        let directory = DirectoryPage {
+            workspace_id: &workspace_id,
+            url_key,
+            workspace_name: &workspace_name,
+            url_path: path,
+            dir: &dir,
+            trusted,
+            recent: &recent,
+        };
+        let listing = ListingQuery::parse(query);
+        render_directory(&directory, &listing, full_path, config, &state.render_cache).await
+    } else if metadata.is_file() {
+        if config.files.filter().is_markdown(&full_path) {
+            let file_path = recent::relative_path(&root_dir, &full_path).unwrap_or_default();
+            recent.record_viewed(file_path.clone());
+            let page = MarkdownPage {
+                workspace_id: &workspace_id,
+                url_key,
+                workspace_name: &workspace_name,
+                url_path: path,
+                file_path: &file_path,
+                trusted,
+                config: &config,
+            };
+            render_markdown_file(&page, &full_path, &state.render_cache, headers).await
         } else {
-            serve_static_file(&full_path).await
+            static_file::serve(&full_path, headers).await
         }
     } else {
         (StatusCode::NOT_FOUND, Html("Not Found")).into_response()
     }
 }
 
-async fn render_directory(
-    workspace_id: &str,
-    workspace_name: &str,
-    full_path: &PathBuf,
-    url_path: &str,
-) -> Response {
-    let Ok(read_dir) = fs::read_dir(full_path) else {
-        return (StatusCode::INTERNAL_SERVER_ERROR, Html("Failed to read directory")).into_response();
-    };
+/// Sorts directories first, then by `key` with the name breaking ties.
+fn sort_entries(entries: &mut [FileEntry], key: SortKey, order: SortOrder) {
+    entries.sort_by(|a, b| {
+        let by_name = || a.name.to_lowercase().cmp(&b.name.to_lowercase());
+        let ordering = match key {
+            SortKey::Name => by_name(),
+            SortKey::Size => a.size_bytes.cmp(&b.size_bytes).then_with(by_name),
+            SortKey::Modified => a.modified_time.cmp(&b.modified_time).then_with(by_name),
+        };
+        let ordering = if order == SortOrder::Desc { ordering.reverse() } else { ordering };
+        b.is_dir.cmp(&a.is_dir).then(ordering)
+    });
+}
+
+#[derive(Debug, PartialEq)]
+enum RecentChange {
+    Changed,
+    Gone,
+    None,
+}
+
+/// How a watcher event affects the recently changed list, for the path at
+/// `index` in the event: the old path of a rename is gone.
+fn recent_change(kind: &EventKind, index: usize) -> RecentChange {
+    match kind {
+        EventKind::Access(_) => RecentChange::None,
+        EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(RenameMode::From)) => RecentChange::Gone,
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) if index == 0 => RecentChange::Gone,
+        _ => RecentChange::Changed,
+    }
+}
 
-    let base_url = format!("/view/{}", workspace_id);
+/// Lists the markdown files and the directories containing markdown in
+/// `full_path`, directories first. With `raw_base_url`, every other file
+/// and directory is listed as well, files linked below it. Blocking:
+/// walks subdirectories.
+fn list_directory(
+    full_path: &std::path::Path,
+    base_url: &str,
+    url_path: &str,
+    config: &config::Config,
+    raw_base_url: Option<&str>,
+) -> std::io::Result<Vec<FileEntry>> {
+    let filter = config.files.filter();
 
-    let mut entries: Vec<FileEntry> = read_dir
+    let mut entries: Vec<FileEntry> = fs::read_dir(full_path)?
         .filter_map(|entry| entry.ok())
         .filter_map(|entry| {
             let name = entry.file_name().to_string_lossy().to_string();
-            if name.starts_with('.') {
+            if name.starts_with('.') || filter.is_ignored(&name) {
                 return None;
             }
 
             let entry_full_path = entry.path();
             let metadata = entry.metadata().ok()?;
             let is_dir = metadata.is_dir();
+            let is_raw = !is_dir && !filter.is_markdown(&entry_full_path);
 
-            if is_dir {
-                if !contains_markdown(&entry_full_path) {
-                    return None;
-                }
-            } else {
-                let is_md = entry_full_path.extension().and_then(|e| e.to_str()) == Some("md");
-                if !is_md {
+            if raw_base_url.is_none() {
+                if is_dir {
+                    if !contains_markdown(&entry_full_path, &filter) {
+                        return None;
+                    }
+                } else if is_raw {
                     return None;
                 }
             }
@@ -516,12 +1606,13 @@ async fn render_directory(
             } else {
                 format_file_size(metadata.len())
             };
-            let modified = metadata
-                .modified()
-                .ok()
-                .map(format_datetime)
-                .unwrap_or_else(|| "-".to_string());
+            let modified_time = metadata.modified().ok();
+            let modified = modified_time.map(format_datetime).unwrap_or_else(|| "-".to_string());
 
+            let base_url = match raw_base_url {
+                Some(raw_base_url) if is_raw => raw_base_url,
+                _ => base_url,
+            };
             let entry_path = if url_path.is_empty() {
                 format!("{}/{}", base_url, name)
             } else {
@@ -532,21 +1623,119 @@ async fn render_directory(
                 name,
                 path: entry_path,
                 is_dir,
+                is_raw,
                 size,
                 modified,
+                size_bytes: if is_dir { 0 } else { metadata.len() },
+                modified_time,
             })
         })
         .collect();
 
-    entries.sort_by(|a, b| match (a.is_dir, b.is_dir) {
-        (true, false) => std::cmp::Ordering::Less,
-        (false, true) => std::cmp::Ordering::Greater,
-        _ => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
-    });
+    sort_entries(&mut entries, SortKey::Name, SortOrder::Asc);
+    Ok(entries)
+}
+
+/// What a rendered directory listing belongs to.
+struct DirectoryPage<'a> {
+    workspace_id: &'a str,
+    /// Id or alias used in links.
+    url_key: &'a str,
+    workspace_name: &'a str,
+    url_path: &'a str,
+    /// Canonical path of the directory relative to the workspace root.
+    dir: &'a str,
+    trusted: bool,
+    recent: &'a RecentFiles,
+}
+
+/// Up to `DIRECTORY_RECENT` entries of `entries` below `dir`.
+fn recent_links(entries: Vec<recent::RecentEntry>, dir: &str, base_url: &str) -> Vec<RecentLink> {
+    entries
+        .into_iter()
+        .filter(|entry| dir.is_empty() || entry.path.starts_with(&format!("{}/", dir)))
+        .take(DIRECTORY_RECENT)
+        .map(|entry| RecentLink {
+            url: format!("{}/{}", base_url, entry.path),
+            name: entry.path[if dir.is_empty() { 0 } else { dir.len() + 1 }..].to_string(),
+            time: format_datetime(entry.time),
+        })
+        .collect()
+}
+
+async fn render_directory(
+    page: &DirectoryPage<'_>,
+    query: &ListingQuery,
+    full_path: PathBuf,
+    config: Arc<config::Config>,
+    cache: &RenderCache,
+) -> Response {
+    let DirectoryPage { workspace_id, url_key, workspace_name, url_path, dir, trusted, recent } = *page;
+    let base_url = format!("/view/{}", url_key);
+
+    // Links in the index document are relative to its directory.
+    let dir_url = if dir.is_empty() { format!("{}/", base_url) } else { format!("{}/{}/", base_url, dir) };
+    let index_path = if query.format == ListingFormat::Html {
+        let full_path = full_path.clone();
+        let config = config.clone();
+        tokio::task::spawn_blocking(move || find_index_file(&full_path, &config.view.index_files, &config.files.filter()))
+            .await
+            .ok()
+            .flatten()
+    } else {
+        None
+    };
+    // Checked before listing, which walks every subdirectory.
+    if config.view.redirect_to_index && !query.explicit {
+        if let Some(name) = index_path.as_ref().and_then(|path| path.file_name()) {
+            let url = format!("{}{}", dir_url, name.to_string_lossy());
+            return Redirect::temporary(&encode_path(&url)).into_response();
+        }
+    }
+
+    let listing = {
+        let base_url = base_url.clone();
+        let raw_base_url = query.all.then(|| format!("/_raw/{}", url_key));
+        let url_path = url_path.to_string();
+        let config = config.clone();
+        tokio::task::spawn_blocking(move || list_directory(&full_path, &base_url, &url_path, &config, raw_base_url.as_deref()))
+            .await
+    };
+    let Ok(Ok(mut entries)) = listing else {
+        return (StatusCode::INTERNAL_SERVER_ERROR, Html("Failed to read directory")).into_response();
+    };
+    query.apply(&mut entries);
+
+    if query.format == ListingFormat::Json {
+        let entries = entries.into_iter().map(|entry| ListingEntry::new(dir, entry)).collect();
+        return Json(ListingResponse { path: dir.to_string(), entries }).into_response();
+    }
+
+    // Subdirectories are listed the same way.
+    let carried = query.carried();
+    for entry in entries.iter_mut().filter(|entry| entry.is_dir) {
+        entry.path.push_str(&carried);
+    }
+
+    let mut index = None;
+    if let Some(index_path) = index_path {
+        let name = index_path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        let url = format!("{}{}", dir_url, name);
+        if let Ok(metadata) = tokio::fs::metadata(&index_path).await {
+            if metadata.len() <= config.markdown.max_file_size {
+                let variant = format!("{}:{}:{}", workspace_id, if trusted { "t" } else { "u" }, dir_url);
+                let rendered = render_cached(&index_path, &metadata, &variant, cache, &config.markdown, trusted, Some(dir_url))
+                    .await;
+                if let Ok(content) = rendered {
+                    index = Some(IndexDocument { name, url, content: content.to_string() });
+                }
+            }
+        }
+    }
 
-    let breadcrumbs = generate_breadcrumbs(workspace_id, workspace_name, url_path);
+    let breadcrumbs = generate_breadcrumbs(url_key, workspace_name, url_path);
     let has_parent = !url_path.is_empty();
-    let parent_path = if has_parent {
+    let mut parent_path = if has_parent {
         let parts: Vec<&str> = url_path.split('/').filter(|s| !s.is_empty()).collect();
         if parts.len() <= 1 {
             base_url
@@ -556,40 +1745,87 @@ async fn render_directory(
     } else {
         base_url
     };
+    parent_path.push_str(&carried);
 
+    let workspace_url = format!("/view/{}", url_key);
     let template = DirectoryTemplate {
         breadcrumbs,
         entries,
+        index,
+        recent: [
+            ("Recently viewed", recent.viewed()),
+            ("Recently changed", recent.changed()),
+        ]
+        .into_iter()
+        .map(|(title, entries)| RecentSection {
+            title,
+            links: recent_links(entries, dir, &workspace_url),
+        })
+        .filter(|section| !section.links.is_empty())
+        .collect(),
         has_parent,
         parent_path,
+        sort_name: SortHeader::new(query, SortKey::Name),
+        sort_size: SortHeader::new(query, SortKey::Size),
+        sort_modified: SortHeader::new(query, SortKey::Modified),
+        sort: query.sort.as_str(),
+        order: query.order.as_str(),
+        filter: query.filter.clone(),
+        show_all: query.all,
         workspace_id: workspace_id.to_string(),
         workspace_name: workspace_name.to_string(),
+        theme: config.view.theme.as_str(),
+        csp_nonce: security::generate_nonce(),
     };
 
-    match template.render() {
-        Ok(html) => Html(html).into_response(),
-        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Html("Template error")).into_response(),
-    }
+    render_page(&template, &template.csp_nonce)
+}
+
+/// What a rendered markdown page belongs to.
+struct MarkdownPage<'a> {
+    workspace_id: &'a str,
+    /// Id or alias used in links.
+    url_key: &'a str,
+    workspace_name: &'a str,
+    url_path: &'a str,
+    /// Canonical path of the file relative to the workspace root.
+    file_path: &'a str,
+    trusted: bool,
+    config: &'a config::Config,
 }
 
 async fn render_markdown_file(
-    workspace_id: &str,
-    workspace_name: &str,
+    page: &MarkdownPage<'_>,
     full_path: &PathBuf,
-    url_path: &str,
+    cache: &RenderCache,
+    headers: &HeaderMap,
 ) -> Response {
-    let Ok(content) = fs::read_to_string(full_path) else {
+    let MarkdownPage { workspace_id, url_key, workspace_name, url_path, file_path, trusted, config } = *page;
+
+    let Ok(metadata) = tokio::fs::metadata(full_path).await else {
         return (StatusCode::INTERNAL_SERVER_ERROR, Html("Failed to read file")).into_response();
     };
+    if metadata.len() > config.markdown.max_file_size {
+        debug!(path = %full_path.display(), size = metadata.len(), "Too large to render, serving raw");
+        return static_file::serve_plain_text(full_path, headers).await;
+    }
+    let variant = format!("{}:{}", workspace_id, if trusted { "t" } else { "u" });
+    // The page around the document shows the workspace name and links
+    // through the id or alias, both of which can change at runtime.
+    let page_inputs = format!("{}\0{}\0{}", url_key, workspace_name, config.view.theme.as_str());
+    let page_variant = format!("{}:{:x}", variant, fnv1a(page_inputs.as_bytes()));
+    let validators = Validators::for_rendered(&metadata, &page_variant);
+    if validators.is_not_modified(headers) {
+        return validators.not_modified();
+    }
 
-    let html_content = render_markdown(&content);
-    let breadcrumbs = generate_breadcrumbs(workspace_id, workspace_name, url_path);
+    let html_content = match render_cached(full_path, &metadata, &variant, cache, &config.markdown, trusted, None).await {
+        Ok(html) => html,
+        Err(message) => return (StatusCode::INTERNAL_SERVER_ERROR, Html(message)).into_response(),
+    };
+    let breadcrumbs = generate_breadcrumbs(url_key, workspace_name, url_path);
 
-    let metadata = fs::metadata(full_path).ok();
-    let file_size = metadata
-        .as_ref()
-        .map(|m| format_file_size(m.len()))
-        .unwrap_or_else(|| "-".to_string());
+    let file_size = format_file_size(metadata.len());
 
     let filename = full_path
         .file_name()
@@ -597,55 +1833,91 @@ async fn render_markdown_file(
         .unwrap_or("unknown")
         .to_string();
 
-    let raw_path = format!("/_raw/{}/{}", workspace_id, url_path.trim_start_matches('/'));
+    let raw_path = format!("/_raw/{}/{}", url_key, url_path.trim_start_matches('/'));
 
     let template = MarkdownTemplate {
         breadcrumbs,
-        content: html_content,
+        content: html_content.to_string(),
         filename,
         file_size,
         raw_path,
+        tree_api: format!("/api/tree/{}", url_key),
+        file_path: file_path.to_string(),
         workspace_id: workspace_id.to_string(),
         workspace_name: workspace_name.to_string(),
+        theme: config.view.theme.as_str(),
+        csp_nonce: security::generate_nonce(),
     };
 
-    match template.render() {
-        Ok(html) => Html(html).into_response(),
-        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Html("Template error")).into_response(),
+    let mut response = render_page(&template, &template.csp_nonce);
+    if response.status().is_success() {
+        validators.apply(&mut response);
     }
+    response
 }
 
-async fn serve_static_file(full_path: &PathBuf) -> Response {
-    let Ok(content) = fs::read(full_path) else {
-        return (StatusCode::INTERNAL_SERVER_ERROR, Html("Failed to read file")).into_response();
+/// Rendered HTML of a markdown file, sanitized unless `trusted`, reused
+/// from `cache` while the file is unchanged. `link_base` is passed on to
+/// `render_markdown`.
+async fn render_cached(
+    full_path: &std::path::Path,
+    metadata: &std::fs::Metadata,
+    variant: &str,
+    cache: &RenderCache,
+    markdown_config: &config::MarkdownConfig,
+    trusted: bool,
+    link_base: Option<String>,
+) -> Result<Arc<str>, &'static str> {
+    if let Some(html) = cache.get(full_path, metadata, variant) {
+        return Ok(html);
+    }
+    let Ok(content) = tokio::fs::read_to_string(full_path).await else {
+        return Err("Failed to read file");
     };
-
-    let mime = mime_guess::from_path(full_path).first_or_octet_stream();
-    let content_type = if mime.type_() == "text" {
-        format!("{}; charset=utf-8", mime)
-    } else {
-        mime.to_string()
+    // Rendering and sanitizing large documents is CPU-bound.
+    let markdown_config = markdown_config.clone();
+    let rendered = tokio::task::spawn_blocking(move || {
+        let _timer = METRICS.render_duration.start_timer();
+        let html = render_markdown(&content, &markdown_config, link_base.as_deref());
+        if trusted {
+            html
+        } else {
+            sanitize::sanitize_html(&html)
+        }
+    })
+    .await;
+    let Ok(html) = rendered else {
+        return Err("Failed to render file");
     };
-
-    ([(header::CONTENT_TYPE, content_type)], content).into_response()
+    let html: Arc<str> = Arc::from(html);
+    cache.insert(full_path.to_path_buf(), metadata, variant, html.clone());
+    Ok(html)
 }
 
 async fn handle_raw(
     State(state): State<AppState>,
     Path((workspace_id, path)): Path<(String, String)>,
+    headers: HeaderMap,
 ) -> Response {
-    let inner = state.inner.read().await;
-    let Some(workspace) = inner.workspaces.get(&workspace_id) else {
+    let Some(root_dir) = state
+        .inner
+        .read()
+        .await
+        .resolve(&workspace_id)
+        .map(|w| w.root_dir.clone())
+    else {
         return (StatusCode::NOT_FOUND, Html("Workspace not found")).into_response();
     };
 
-    let Some(full_path) = validate_path(&workspace.root_dir, &path) else {
+    let Some(full_path) = validate_path(&root_dir, &path).await else {
         return (StatusCode::NOT_FOUND, Html("Not Found")).into_response();
     };
-    drop(inner);
+    if state.register_policy.is_denied(&full_path) {
+        return (StatusCode::FORBIDDEN, Html("Forbidden")).into_response();
+    }
 
-    if full_path.is_file() {
-        serve_static_file(&full_path).await
+    if tokio::fs::metadata(&full_path).await.is_ok_and(|m| m.is_file()) {
+        static_file::serve(&full_path, &headers).await
     } else {
         (StatusCode::NOT_FOUND, Html("Not Found")).into_response()
     }
@@ -656,17 +1928,29 @@ async fn handle_reload(
     Path(workspace_id): Path<String>,
 ) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
     let mut rx = state.reload_tx.subscribe();
-    let ws_id = workspace_id.clone();
+    let ws_id = match state.inner.read().await.resolve(&workspace_id) {
+        Some(workspace) => workspace.id.clone(),
+        None => workspace_id,
+    };
+    let shutdown = state.shutdown.clone();
 
     let stream = async_stream::stream! {
+        let _connected = GaugeGuard::new(&METRICS.sse_clients);
         loop {
-            match rx.recv().await {
+            let received = tokio::select! {
+                _ = shutdown.cancelled() => break,
+                received = rx.recv() => received,
+            };
+            match received {
                 Ok(id) => {
                     if id == ws_id {
                         yield Ok(Event::default().event("reload").data("reload"));
                     }
                 }
-                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    METRICS.broadcast_lagged.with_label_values(&["reload"]).inc_by(n);
+                    continue;
+                }
                 Err(broadcast::error::RecvError::Closed) => break,
             }
         }
@@ -680,20 +1964,35 @@ async fn handle_ws(ws: WebSocketUpgrade, State(state): State<AppState>) -> Respo
 }
 
 async fn handle_ws_connection(socket: WebSocket, state: AppState) {
+    state.ws_clients.send_modify(|n| *n += 1);
+    let _connected = GaugeGuard::new(&METRICS.ws_clients);
     let (mut sender, mut receiver) = socket.split();
     let mut ws_rx = state.ws_tx.subscribe();
+    let shutdown = state.shutdown.clone();
 
     let send_task = tokio::spawn(async move {
         loop {
-            match ws_rx.recv().await {
+            let received = tokio::select! {
+                _ = shutdown.cancelled() => {
+                    let _ = sender.send(Message::Close(None)).await;
+                    break;
+                }
+                received = ws_rx.recv() => received,
+            };
+            match received {
                 Ok(cmd) => {
                     if let Ok(json) = serde_json::to_string(&cmd) {
-                        if sender.send(Message::Text(json.into())).await.is_err() {
+                        if let Err(e) = sender.send(Message::Text(json.into())).await {
+                            debug!(error = %e, "WebSocket send failed, closing");
                             break;
                         }
                     }
                 }
-                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    warn!(skipped = n, "WebSocket client lagging, dropped commands");
+                    METRICS.broadcast_lagged.with_label_values(&["ws"]).inc_by(n);
+                    continue;
+                }
                 Err(broadcast::error::RecvError::Closed) => break,
             }
         }
@@ -711,55 +2010,94 @@ async fn handle_ws_connection(socket: WebSocket, state: AppState) {
         _ = send_task => {}
         _ = recv_task => {}
     }
+
+    state.ws_clients.send_modify(|n| *n -= 1);
 }
 
-// Root page with workspace list
-async fn handle_root(State(state): State<AppState>) -> Response {
-    let inner = state.inner.read().await;
-    let workspaces: Vec<_> = inner.workspaces.values().collect();
+/// Resolves once at least one browser tab has connected and then none has
+/// been connected for `grace`.
+async fn wait_for_last_tab(mut clients: tokio::sync::watch::Receiver<usize>, grace: std::time::Duration) {
+    if clients.wait_for(|n| *n > 0).await.is_err() {
+        return;
+    }
+    loop {
+        if clients.wait_for(|n| *n == 0).await.is_err() {
+            return;
+        }
+        match tokio::time::timeout(grace, clients.wait_for(|n| *n > 0)).await {
+            Err(_) => return,
+            Ok(Err(_)) => return,
+            Ok(Ok(_)) => continue,
+        }
+    }
+}
 
-    let workspace_list = if workspaces.is_empty() {
-        "<p style=\"color:#8b949e;\">No workspaces registered yet.</p>".to_string()
+/// Registers the directory enclosing `preview.path` and returns the view
+/// URL path for it.
+async fn start_preview(state: &AppState, preview: &Preview) -> Result<String, String> {
+    let target = tokio::fs::canonicalize(&preview.path)
+        .await
+        .map_err(|e| format!("Cannot open {}: {}", preview.path.display(), e))?;
+    let dir = if tokio::fs::metadata(&target).await.is_ok_and(|m| m.is_dir()) {
+        target.clone()
     } else {
-        let items: Vec<String> = workspaces
-            .iter()
-            .map(|ws| {
-                format!(
-                    r#"<li><a href="/view/{}" style="color:#58a6ff;">{}</a> <span style="color:#8b949e;">- {}</span></li>"#,
-                    ws.id, ws.name, ws.root_dir.display()
-                )
-            })
-            .collect();
-        format!("<ul>{}</ul>", items.join("\n"))
-    };
-
-    let html = format!(
-        r#"<!DOCTYPE html>
-<html>
-<head><title>mdv server</title></head>
-<body style="background:#0d1117;color:#c9d1d9;font-family:sans-serif;padding:2rem;">
-<h1>mdv server is running</h1>
-<p>Use your editor plugin to register workspaces and open files.</p>
-<h2>Workspaces</h2>
-{}
-<h2>API endpoints</h2>
-<ul>
-<li>POST /api/workspace/register - Register a workspace</li>
-<li>DELETE /api/workspace/{{id}} - Remove a workspace</li>
-<li>GET /api/active?path=... - Navigate to a file</li>
-<li>GET /api/status - Server status</li>
-</ul>
-</body>
-</html>"#,
-        workspace_list
-    );
+        target.parent().map(PathBuf::from).unwrap_or_else(|| target.clone())
+    };
+
+    let workspace = register_workspace(state, &dir, &WorkspaceOptions::default())
+        .await
+        .map_err(|(_, message)| message.to_string())?;
+    let relative = target.strip_prefix(&dir).unwrap_or(&target).to_string_lossy();
+    Ok(preview_url(&workspace.url, &relative))
+}
 
-    (StatusCode::OK, Html(html)).into_response()
+/// The view URL path of `relative` below a workspace's view URL.
+fn preview_url(workspace_url: &str, relative: &str) -> String {
+    if relative.is_empty() {
+        workspace_url.to_string()
+    } else {
+        encode_path(&format!("{}/{}", workspace_url, relative))
+    }
 }
 
 #[tokio::main]
 async fn main() {
-    let args = Args::parse();
+    let mut cli = Cli::parse();
+
+    let mut config = config::Config::load(cli.args.config.as_deref()).unwrap_or_else(|message| {
+        eprintln!("Error: {}", message);
+        std::process::exit(1);
+    });
+    cli.args.apply_config(&mut config);
+
+    let preview = cli.path.map(|path| Preview {
+        path,
+        open: cli.open,
+        once: cli.once,
+    });
+
+    match cli.command {
+        None | Some(cli::Command::Serve) if cli.daemon => {
+            if let Err(message) = daemon::start(&cli.args).await {
+                eprintln!("Error: {}", message);
+                std::process::exit(1);
+            }
+        }
+        None | Some(cli::Command::Serve) => serve(cli.args, config, preview).await,
+        Some(command) => {
+            if let Err(message) = cli::run(command, &cli.args, &config).await {
+                eprintln!("Error: {}", message);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+async fn serve(args: Args, config: config::Config, preview: Option<Preview>) {
+    if let Err(message) = logging::init(args.log_level.as_deref(), args.log_format) {
+        eprintln!("Error: {}", message);
+        std::process::exit(1);
+    }
 
     let (reload_tx, _) = broadcast::channel::<String>(16);
     let (ws_tx, _) = broadcast::channel::<WsCommand>(16);
@@ -770,31 +2108,291 @@ async fn main() {
         })),
         reload_tx,
         ws_tx,
+        origin_policy: Arc::new(OriginPolicy::new(args.host(), &args.allow_origins)),
+        register_policy: Arc::new(RegisterPolicy::new(&args.allow_roots, &args.deny_roots)),
+        ws_clients: Arc::new(tokio::sync::watch::channel(0).0),
+        shutdown: CancellationToken::new(),
+        api_token: Arc::new(security::generate_token()),
+        config: Arc::new(config),
+        render_cache: Arc::new(RenderCache::default()),
     };
+    let server_state = state.clone();
 
     let app = Router::new()
-        .route("/", get(handle_root))
+        .route("/", get(dashboard::handle_root))
         .route("/api/workspace/register", post(api_register))
-        .route("/api/workspace/{id}", delete(api_unregister))
+        .route("/api/workspace/{id}", delete(api_unregister).patch(api_update))
         .route("/api/active", get(api_active))
         .route("/api/status", get(api_status))
+        .route("/api/recent", get(api_recent))
+        .route("/api/tree/{workspace_id}", get(api_tree))
         .route("/api/remote/scroll", get(api_scroll))
+        .route("/api/shutdown", post(api_shutdown))
+        .route("/metrics", get(metrics::handle_metrics))
         .route("/ws", get(handle_ws))
         .route("/view/{workspace_id}", get(handle_view_root))
         .route("/view/{workspace_id}/{*path}", get(handle_view_path))
         .route("/_reload/{workspace_id}", get(handle_reload))
         .route("/_raw/{workspace_id}/{*path}", get(handle_raw))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            security::enforce_origin_policy,
+        ))
+        .layer(middleware::from_fn(security::set_security_headers))
+        .layer(middleware::from_fn(metrics::track_requests))
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(|req: &axum::http::Request<_>| {
+                    let route = req
+                        .extensions()
+                        .get::<axum::extract::MatchedPath>()
+                        .map(|p| p.as_str().to_string())
+                        .unwrap_or_default();
+                    info_span!("request", method = %req.method(), path = %req.uri().path(), route)
+                })
+                .on_response(DefaultOnResponse::new().level(Level::DEBUG)),
+        )
         .with_state(state);
 
-    let addr = format!("{}:{}", args.host, args.port);
-    let listener = tokio::net::TcpListener::bind(&addr).await.unwrap_or_else(|e| {
-        eprintln!("Error: Cannot bind to {}: {}", addr, e);
-        std::process::exit(1);
+    let compression = &server_state.config.compression;
+    let app = if compression.enabled {
+        app.layer(compression_layer(compression.min_size))
+            .layer(middleware::map_response(http_cache::weaken_encoded_etag))
+    } else {
+        app
+    };
+
+    let tcp_port = args.tcp_port();
+    let shutdown = server_state.shutdown.clone();
+
+    let runtime_dir = runtime::runtime_dir();
+    let instance = args.instance_name();
+    let _instance_lock = match runtime::lock_instance(&runtime_dir, &instance) {
+        Ok(Some(lock)) => Some(lock),
+        Ok(None) => {
+            error!("{}", runtime::describe_running(&runtime_dir, &instance));
+            std::process::exit(1);
+        }
+        Err(e) => {
+            warn!(dir = %runtime_dir.display(), error = %e, "Cannot take the single-instance lock");
+            None
+        }
+    };
+
+    let tls_config = tls_paths(&args).map(|(cert, key)| {
+        listener::load_tls_config(&cert, &key).unwrap_or_else(|e| {
+            error!(cert = %cert.display(), error = %e, "Cannot load TLS certificate");
+            std::process::exit(1);
+        })
+    });
+
+    let mut servers: Vec<ServerFuture> = Vec::new();
+
+    if let Some(port) = tcp_port {
+        let addr = format!("{}:{}", args.host(), port);
+        let listener = tokio::net::TcpListener::bind(&addr).await.unwrap_or_else(|e| {
+            error!(%addr, error = %e, "Cannot bind");
+            std::process::exit(1);
+        });
+        match tls_config {
+            Some(config) => {
+                let listener = listener::TlsListener::new(listener, config).unwrap_or_else(|e| {
+                    error!(%addr, error = %e, "Cannot bind");
+                    std::process::exit(1);
+                });
+                info!("mdv server listening at https://{}", addr);
+                servers.push(Box::pin(
+                    axum::serve(listener, app.clone())
+                        .with_graceful_shutdown(shutdown.clone().cancelled_owned())
+                        .into_future(),
+                ));
+            }
+            None => {
+                info!("mdv server listening at http://{}", addr);
+                servers.push(Box::pin(
+                    axum::serve(listener, app.clone())
+                        .with_graceful_shutdown(shutdown.clone().cancelled_owned())
+                        .into_future(),
+                ));
+            }
+        }
+    }
+
+    match &args.socket {
+        #[cfg(unix)]
+        Some(path) => {
+            let listener = listener::bind_unix_socket(path).unwrap_or_else(|e| {
+                error!(socket = %path.display(), error = %e, "Cannot bind");
+                std::process::exit(1);
+            });
+            info!("mdv server listening at unix:{}", path.display());
+            servers.push(Box::pin(
+                axum::serve(listener, app)
+                    .with_graceful_shutdown(shutdown.clone().cancelled_owned())
+                    .into_future(),
+            ));
+        }
+        #[cfg(not(unix))]
+        Some(_) => {
+            error!("--socket is only supported on Unix platforms");
+            std::process::exit(1);
+        }
+        None => {}
+    }
+
+    let info = runtime::RuntimeInfo {
+        pid: std::process::id(),
+        port: tcp_port,
+        socket: args.socket.clone(),
+        token: server_state.api_token.to_string(),
+    };
+    if let Err(e) = runtime::write_info(&runtime_dir, &instance, &info) {
+        warn!(dir = %runtime_dir.display(), error = %e, "Cannot write runtime files");
+    }
+
+    for saved in runtime::read_registry(&runtime_dir, &instance) {
+        let options = WorkspaceOptions {
+            trusted: Some(saved.trusted),
+            name: Some(saved.name),
+            alias: saved.alias,
+        };
+        if let Err((_, message)) = register_workspace(&server_state, &saved.path, &options).await {
+            warn!(workspace = %saved.path.display(), "Cannot restore workspace: {}", message);
+        }
+    }
+
+    tokio::spawn({
+        let shutdown = shutdown.clone();
+        async move {
+            wait_for_signal().await;
+            shutdown.cancel();
+        }
     });
 
-    println!("mdv server listening at http://{}", addr);
+    if let Some(preview) = &preview {
+        let url_path = start_preview(&server_state, preview).await.unwrap_or_else(|message| {
+            error!("{}", message);
+            std::process::exit(1);
+        });
+        match cli::browser_base_url(&args) {
+            Some(base) => {
+                let url = format!("{}{}", base, url_path);
+                println!("Preview: {}", url);
+                if preview.open {
+                    cli::open_browser(&url);
+                }
+            }
+            None => println!("Preview: {} (server has no TCP listener)", url_path),
+        }
+        if preview.once {
+            let clients = server_state.ws_clients.subscribe();
+            let shutdown = shutdown.clone();
+            tokio::spawn(async move {
+                wait_for_last_tab(clients, ONCE_GRACE_PERIOD).await;
+                info!("Last browser tab closed, exiting");
+                shutdown.cancel();
+            });
+        }
+    }
+
+    let forced = async {
+        shutdown.cancelled().await;
+        tokio::time::sleep(SHUTDOWN_TIMEOUT).await;
+    };
+    tokio::select! {
+        result = futures::future::try_join_all(servers) => {
+            if let Err(e) = result {
+                error!(error = %e, "Server error");
+            }
+        }
+        _ = forced => warn!("Connections did not close in time"),
+    }
+
+    let workspaces: Vec<Workspace> = server_state
+        .inner
+        .write()
+        .await
+        .workspaces
+        .drain()
+        .map(|(_, w)| w)
+        .collect();
+    let saved: Vec<runtime::SavedWorkspace> = workspaces
+        .iter()
+        .map(|w| runtime::SavedWorkspace {
+            path: w.root_dir.clone(),
+            name: w.name.clone(),
+            alias: w.alias.clone(),
+            trusted: w.trusted,
+        })
+        .collect();
+    if let Err(e) = runtime::write_registry(&runtime_dir, &instance, &saved) {
+        warn!(dir = %runtime_dir.display(), error = %e, "Cannot save the workspace registry");
+    }
+    stop_watchers(workspaces).await;
+
+    runtime::remove_info(&runtime_dir, &instance);
+    #[cfg(unix)]
+    if let Some(path) = &args.socket {
+        let _ = fs::remove_file(path);
+    }
+    info!("mdv server stopped");
+}
+
+/// Compresses responses above `min_size` bytes with the best encoding the
+/// client accepts. Skips media and archives that are already compressed
+/// (images are skipped by the default predicate), live-reload streams,
+/// and partial content, whose byte ranges refer to the uncompressed file.
+fn compression_layer(min_size: u16) -> CompressionLayer<impl Predicate> {
+    let predicate = SizeAbove::new(min_size)
+        .and(NotForContentType::GRPC)
+        .and(NotForContentType::IMAGES)
+        .and(NotForContentType::SSE)
+        .and(NotForContentType::const_new("video/"))
+        .and(NotForContentType::const_new("audio/"))
+        .and(NotForContentType::const_new("font/woff"))
+        .and(NotForContentType::const_new("application/pdf"))
+        .and(NotForContentType::const_new("application/zip"))
+        .and(NotForContentType::const_new("application/gzip"))
+        .and(NotForContentType::const_new("application/zstd"))
+        .and(NotForContentType::const_new("application/epub+zip"))
+        .and(NotForContentType::const_new("application/java-archive"))
+        .and(NotForContentType::const_new("application/vnd.openxmlformats"))
+        .and(NotForContentType::const_new("application/vnd.rar"))
+        .and(NotForContentType::const_new("application/x-7z-compressed"))
+        .and(NotForContentType::const_new("application/x-bzip"))
+        .and(NotForContentType::const_new("application/x-gzip"))
+        .and(NotForContentType::const_new("application/x-rar-compressed"))
+        .and(NotForContentType::const_new("application/x-xz"))
+        .and(
+            |status: StatusCode, _: axum::http::Version, _: &HeaderMap, _: &axum::http::Extensions| {
+                status != StatusCode::PARTIAL_CONTENT
+            },
+        );
+    CompressionLayer::new().compress_when(predicate)
+}
+
+/// Resolves on Ctrl-C or, on Unix, SIGTERM.
+async fn wait_for_signal() {
+    let ctrl_c = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(_) => std::future::pending::<()>().await,
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
 
-    axum::serve(listener, app).await.unwrap();
+    tokio::select! {
+        _ = ctrl_c => info!("Received Ctrl-C, shutting down"),
+        _ = terminate => info!("Received SIGTERM, shutting down"),
+    }
 }
 
 #[cfg(test)]
@@ -803,6 +2401,10 @@ mod tests {
     use std::fs::{self, File};
     use tempfile::TempDir;
 
+    fn default_filter() -> FileFilter {
+        config::FilesConfig::default().filter()
+    }
+
     #[test]
     fn test_format_file_size_bytes() {
         assert_eq!(format_file_size(0), "0 B");
@@ -832,28 +2434,48 @@ mod tests {
     #[test]
     fn test_generate_workspace_id_format() {
         let path = PathBuf::from("/home/user/project");
-        let id = generate_workspace_id(&path);
+        let id = generate_workspace_id(&path, 8);
         assert!(id.starts_with("project-"));
-        assert!(id.len() > 8); // "project-" + hash
+        assert_eq!(id.len(), "project-".len() + 8);
+        assert_eq!(generate_workspace_id(&path, 16).len(), "project-".len() + 16);
     }
 
     #[test]
-    fn test_generate_workspace_id_deterministic() {
+    fn test_generate_workspace_id_stable() {
+        // Must never change: ids appear in bookmarked URLs.
+        assert_eq!(fnv1a(b""), 0xcbf29ce484222325);
+        assert_eq!(fnv1a(b"a"), 0xaf63dc4c8601ec8c);
         let path = PathBuf::from("/home/user/project");
-        let id1 = generate_workspace_id(&path);
-        let id2 = generate_workspace_id(&path);
-        assert_eq!(id1, id2);
+        assert_eq!(generate_workspace_id(&path, 8), "project-f8a71a04");
+        assert_eq!(generate_workspace_id(&path, 16), "project-f8a71a04e8340307");
     }
 
     #[test]
     fn test_generate_workspace_id_different_paths() {
         let path1 = PathBuf::from("/home/user/project1");
         let path2 = PathBuf::from("/home/user/project2");
-        let id1 = generate_workspace_id(&path1);
-        let id2 = generate_workspace_id(&path2);
+        let id1 = generate_workspace_id(&path1, 8);
+        let id2 = generate_workspace_id(&path2, 8);
         assert_ne!(id1, id2);
     }
 
+    #[test]
+    fn test_generate_workspace_id_escapes_name() {
+        let id = generate_workspace_id(std::path::Path::new("/home/user/my notes#1"), 8);
+        assert!(id.starts_with("my-notes-1-"));
+    }
+
+    #[test]
+    fn test_is_valid_alias() {
+        assert!(is_valid_alias("docs"));
+        assert!(is_valid_alias("api-v2.1_notes"));
+        assert!(!is_valid_alias(""));
+        assert!(!is_valid_alias("a/b"));
+        assert!(!is_valid_alias("a b"));
+        assert!(!is_valid_alias(".."));
+        assert!(!is_valid_alias(&"a".repeat(65)));
+    }
+
     #[test]
     fn test_generate_breadcrumbs_root() {
         let crumbs = generate_breadcrumbs("ws-123", "myproject", "");
@@ -879,7 +2501,7 @@ mod tests {
     #[test]
     fn test_render_markdown_basic() {
         let md = "# Hello\n\nWorld";
-        let html = render_markdown(md);
+        let html = render_markdown(md, &config::MarkdownConfig::default(), None);
         assert!(html.contains("<h1>"));
         assert!(html.contains("Hello"));
         assert!(html.contains("<p>"));
@@ -888,7 +2510,7 @@ mod tests {
     #[test]
     fn test_render_markdown_table() {
         let md = "| A | B |\n|---|---|\n| 1 | 2 |";
-        let html = render_markdown(md);
+        let html = render_markdown(md, &config::MarkdownConfig::default(), None);
         assert!(html.contains("<table>"));
         assert!(html.contains("<th>"));
     }
@@ -896,45 +2518,131 @@ mod tests {
     #[test]
     fn test_render_markdown_strikethrough() {
         let md = "~~deleted~~";
-        let html = render_markdown(md);
+        let html = render_markdown(md, &config::MarkdownConfig::default(), None);
         assert!(html.contains("<del>"));
     }
 
     #[test]
     fn test_render_markdown_tasklist() {
         let md = "- [x] done\n- [ ] todo";
-        let html = render_markdown(md);
+        let html = render_markdown(md, &config::MarkdownConfig::default(), None);
+        assert!(html.contains("checked"));
+        assert!(html.contains("checkbox"));
+    }
+
+    #[test]
+    fn test_render_markdown_sanitized_script() {
+        let md = "# Title\n\n<script>alert(1)</script>\n\ntext <img src=\"a.png\" onerror=\"alert(2)\">";
+        let html = sanitize::sanitize_html(&render_markdown(md, &config::MarkdownConfig::default(), None));
+        assert!(html.contains("<h1>"));
+        assert!(!html.contains("<script"));
+        assert!(!html.contains("onerror"));
+        assert!(html.contains("a.png"));
+    }
+
+    #[test]
+    fn test_render_markdown_sanitized_keeps_tasklist() {
+        let md = "- [x] done\n- [ ] todo";
+        let html = sanitize::sanitize_html(&render_markdown(md, &config::MarkdownConfig::default(), None));
         assert!(html.contains("checked"));
         assert!(html.contains("checkbox"));
     }
 
     #[test]
-    fn test_validate_path_valid() {
+    fn test_render_markdown_rebases_relative_links() {
+        let md = "[a](guide/a.md) [b](/abs) [c](#top) [d](https://example.com/x) [e](mailto:x@y.z)\n\n![i](img/logo.png)";
+        let html = render_markdown(md, &config::MarkdownConfig::default(), Some("/view/ws/docs/"));
+        assert!(html.contains("href=\"/view/ws/docs/guide/a.md\""));
+        assert!(html.contains("href=\"/abs\""));
+        assert!(html.contains("href=\"#top\""));
+        assert!(html.contains("href=\"https://example.com/x\""));
+        assert!(html.contains("href=\"mailto:x@y.z\""));
+        assert!(html.contains("src=\"/view/ws/docs/img/logo.png\""));
+    }
+
+    #[test]
+    fn test_find_index_file() {
+        let temp = TempDir::new().unwrap();
+        let filter = config::FilesConfig::default().filter();
+        let names = config::ViewConfig::default().index_files;
+        assert_eq!(find_index_file(temp.path(), &names, &filter), None);
+
+        File::create(temp.path().join("index.md")).unwrap();
+        fs::create_dir(temp.path().join("README.md")).unwrap();
+        assert_eq!(find_index_file(temp.path(), &names, &filter), Some(temp.path().join("index.md")));
+
+        File::create(temp.path().join("Readme.md")).unwrap();
+        assert_eq!(find_index_file(temp.path(), &names, &filter), Some(temp.path().join("Readme.md")));
+    }
+
+    #[test]
+    fn test_encode_path() {
+        assert_eq!(encode_path("/view/ws/docs/README.md"), "/view/ws/docs/README.md");
+        assert_eq!(encode_path("/view/ws/my docs/日本.md"), "/view/ws/my%20docs/%E6%97%A5%E6%9C%AC.md");
+    }
+
+    #[test]
+    fn test_preview_url() {
+        assert_eq!(preview_url("/view/notes", ""), "/view/notes");
+        assert_eq!(preview_url("/view/notes", "README.md"), "/view/notes/README.md");
+        assert_eq!(preview_url("/view/notes", "my notes #1.md"), "/view/notes/my%20notes%20%231.md");
+        assert_eq!(preview_url("/view/notes", "a?b.md"), "/view/notes/a%3Fb.md");
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_last_tab_after_disconnect() {
+        let (tx, rx) = tokio::sync::watch::channel(0usize);
+        let grace = std::time::Duration::from_millis(20);
+        let waiter = tokio::spawn(wait_for_last_tab(rx, grace));
+
+        tx.send_modify(|n| *n += 1);
+        tokio::time::sleep(grace * 2).await;
+        assert!(!waiter.is_finished());
+
+        tx.send_modify(|n| *n -= 1);
+        tokio::time::timeout(grace * 10, waiter).await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_last_tab_survives_reload() {
+        let (tx, rx) = tokio::sync::watch::channel(1usize);
+        let grace = std::time::Duration::from_millis(50);
+        let waiter = tokio::spawn(wait_for_last_tab(rx, grace));
+
+        tx.send_modify(|n| *n -= 1);
+        tokio::time::sleep(grace / 5).await;
+        tx.send_modify(|n| *n += 1);
+        tokio::time::sleep(grace * 2).await;
+        assert!(!waiter.is_finished());
+    }
+
+    #[tokio::test]
+    async fn test_validate_path_valid() {
         let temp = TempDir::new().unwrap();
-        let root = temp.path().to_path_buf();
+        let root = temp.path().canonicalize().unwrap();
         let subdir = root.join("subdir");
         fs::create_dir(&subdir).unwrap();
         File::create(subdir.join("file.md")).unwrap();
 
-        let result = validate_path(&root, "subdir/file.md");
+        let result = validate_path(&root, "subdir/file.md").await;
         assert!(result.is_some());
     }
 
-    #[test]
-    fn test_validate_path_traversal_attack() {
+    #[tokio::test]
+    async fn test_validate_path_traversal_attack() {
         let temp = TempDir::new().unwrap();
-        let root = temp.path().to_path_buf();
+        let root = temp.path().canonicalize().unwrap();
 
-        let result = validate_path(&root, "../../../etc/passwd");
+        let result = validate_path(&root, "../../../etc/passwd").await;
         assert!(result.is_none());
     }
 
-    #[test]
-    fn test_validate_path_nonexistent() {
+    #[tokio::test]
+    async fn test_validate_path_nonexistent() {
         let temp = TempDir::new().unwrap();
-        let root = temp.path().to_path_buf();
+        let root = temp.path().canonicalize().unwrap();
 
-        let result = validate_path(&root, "nonexistent.md");
+        let result = validate_path(&root, "nonexistent.md").await;
         assert!(result.is_none());
     }
 
@@ -944,7 +2652,7 @@ mod tests {
         let md_file = temp.path().join("test.md");
         File::create(&md_file).unwrap();
 
-        assert!(contains_markdown(&md_file));
+        assert!(contains_markdown(&md_file, &default_filter()));
     }
 
     #[test]
@@ -953,7 +2661,7 @@ mod tests {
         let txt_file = temp.path().join("test.txt");
         File::create(&txt_file).unwrap();
 
-        assert!(!contains_markdown(&txt_file));
+        assert!(!contains_markdown(&txt_file, &default_filter()));
     }
 
     #[test]
@@ -961,7 +2669,7 @@ mod tests {
         let temp = TempDir::new().unwrap();
         File::create(temp.path().join("readme.md")).unwrap();
 
-        assert!(contains_markdown(&temp.path().to_path_buf()));
+        assert!(contains_markdown(&temp.path().to_path_buf(), &default_filter()));
     }
 
     #[test]
@@ -969,7 +2677,7 @@ mod tests {
         let temp = TempDir::new().unwrap();
         File::create(temp.path().join("readme.txt")).unwrap();
 
-        assert!(!contains_markdown(&temp.path().to_path_buf()));
+        assert!(!contains_markdown(&temp.path().to_path_buf(), &default_filter()));
     }
 
     #[test]
@@ -979,7 +2687,7 @@ mod tests {
         fs::create_dir(&subdir).unwrap();
         File::create(subdir.join("readme.md")).unwrap();
 
-        assert!(contains_markdown(&temp.path().to_path_buf()));
+        assert!(contains_markdown(&temp.path().to_path_buf(), &default_filter()));
     }
 
     #[test]
@@ -989,6 +2697,260 @@ mod tests {
         fs::create_dir(&hidden).unwrap();
         File::create(hidden.join("secret.md")).unwrap();
 
-        assert!(!contains_markdown(&temp.path().to_path_buf()));
+        assert!(!contains_markdown(&temp.path().to_path_buf(), &default_filter()));
+    }
+
+    #[test]
+    fn test_contains_markdown_skips_ignored() {
+        let temp = TempDir::new().unwrap();
+        let modules = temp.path().join("node_modules");
+        fs::create_dir(&modules).unwrap();
+        File::create(modules.join("readme.md")).unwrap();
+
+        let files = config::FilesConfig {
+            ignore: vec!["node_modules".to_string()],
+            ..Default::default()
+        };
+        assert!(!contains_markdown(&temp.path().to_path_buf(), &files.filter()));
+        assert!(contains_markdown(&temp.path().to_path_buf(), &default_filter()));
+    }
+
+    fn workspaces(roots: &[&str]) -> HashMap<String, Workspace> {
+        roots
+            .iter()
+            .map(|root| {
+                let workspace = Workspace {
+                    id: generate_workspace_id(std::path::Path::new(root), 8),
+                    alias: None,
+                    root_dir: PathBuf::from(root),
+                    name: root.to_string(),
+                    trusted: false,
+                    config: Arc::new(config::Config::default()),
+                    recent: Arc::default(),
+                    scan: Arc::default(),
+                    watcher: None,
+                };
+                (workspace.id.clone(), workspace)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_find_workspace_for_nested_roots() {
+        // Insertion order must not matter.
+        for roots in [["/repo", "/repo/docs"], ["/repo/docs", "/repo"]] {
+            let workspaces = workspaces(&roots);
+            let inner = generate_workspace_id(std::path::Path::new("/repo/docs"), 8);
+            let outer = generate_workspace_id(std::path::Path::new("/repo"), 8);
+            let path = std::path::Path::new("/repo/docs/guide/a.md");
+
+            let found = find_workspace_for_path(&workspaces, path, NestedWorkspaces::Innermost);
+            assert_eq!(found, Some((inner.as_str(), "guide/a.md".to_string())));
+            let found = find_workspace_for_path(&workspaces, path, NestedWorkspaces::Outermost);
+            assert_eq!(found, Some((outer.as_str(), "docs/guide/a.md".to_string())));
+
+            let path = std::path::Path::new("/repo/README.md");
+            let found = find_workspace_for_path(&workspaces, path, NestedWorkspaces::Innermost);
+            assert_eq!(found, Some((outer.as_str(), "README.md".to_string())));
+        }
+    }
+
+    #[test]
+    fn test_allocate_id_on_collision() {
+        let mut inner = AppStateInner { workspaces: workspaces(&["/a/project"]) };
+        let other = std::path::Path::new("/b/project");
+        let short = generate_workspace_id(other, 8);
+
+        // Simulate a short-hash collision with a different directory.
+        let mut existing = inner.workspaces.drain().next().unwrap().1;
+        existing.id = short.clone();
+        inner.workspaces.insert(short.clone(), existing);
+
+        let id = inner.allocate_id(other).unwrap();
+        assert_eq!(id, generate_workspace_id(other, 16));
+        assert_eq!(inner.id_for_root(std::path::Path::new("/a/project")), Some(short));
+        assert_eq!(inner.id_for_root(other), None);
+    }
+
+    #[test]
+    fn test_resolve_alias_and_update() {
+        let mut inner = AppStateInner { workspaces: workspaces(&["/repo", "/notes"]) };
+        let repo = generate_workspace_id(std::path::Path::new("/repo"), 8);
+        let notes = generate_workspace_id(std::path::Path::new("/notes"), 8);
+
+        let options = WorkspaceOptions { alias: Some("r".to_string()), ..Default::default() };
+        inner.update(&repo, &options).unwrap();
+        assert_eq!(inner.resolve("r").map(|w| w.id.as_str()), Some(repo.as_str()));
+        assert_eq!(inner.resolve(&repo).map(|w| w.url_key()), Some("r"));
+
+        // An alias can be neither another workspace's alias nor its id.
+        assert_eq!(inner.update(&notes, &options).unwrap_err().0, StatusCode::CONFLICT);
+        let options = WorkspaceOptions { alias: Some(repo.clone()), ..Default::default() };
+        assert_eq!(inner.update(&notes, &options).unwrap_err().0, StatusCode::CONFLICT);
+        assert!(inner.update(&repo, &WorkspaceOptions { alias: Some("r".to_string()), ..Default::default() }).is_ok());
+
+        inner.update(&repo, &WorkspaceOptions { alias: Some(String::new()), ..Default::default() }).unwrap();
+        assert!(inner.resolve("r").is_none());
+        assert_eq!(inner.resolve(&repo).map(|w| w.url_key()), Some(repo.as_str()));
+    }
+
+    #[test]
+    fn test_find_workspace_requires_component_prefix() {
+        let workspaces = workspaces(&["/repo/docs"]);
+        let path = std::path::Path::new("/repo/docs-old/a.md");
+        assert_eq!(find_workspace_for_path(&workspaces, path, NestedWorkspaces::Innermost), None);
+    }
+
+    #[test]
+    fn test_recent_links_below_directory() {
+        let recent = RecentFiles::default();
+        recent.record_viewed("README.md".to_string());
+        recent.record_viewed("docs/guide.md".to_string());
+        recent.record_viewed("docs-old/a.md".to_string());
+
+        let links = recent_links(recent.viewed(), "docs", "/view/ws");
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].name, "guide.md");
+        assert_eq!(links[0].url, "/view/ws/docs/guide.md");
+        assert_eq!(recent_links(recent.viewed(), "", "/view/ws").len(), 3);
+    }
+
+    #[test]
+    fn test_recent_change_for_renames() {
+        use notify::event::{AccessKind, CreateKind, RemoveKind};
+        let both = EventKind::Modify(ModifyKind::Name(RenameMode::Both));
+        assert_eq!(recent_change(&both, 0), RecentChange::Gone);
+        assert_eq!(recent_change(&both, 1), RecentChange::Changed);
+        let from = EventKind::Modify(ModifyKind::Name(RenameMode::From));
+        assert_eq!(recent_change(&from, 0), RecentChange::Gone);
+        let to = EventKind::Modify(ModifyKind::Name(RenameMode::To));
+        assert_eq!(recent_change(&to, 0), RecentChange::Changed);
+        assert_eq!(recent_change(&EventKind::Create(CreateKind::File), 0), RecentChange::Changed);
+        assert_eq!(recent_change(&EventKind::Remove(RemoveKind::File), 0), RecentChange::Gone);
+        assert_eq!(recent_change(&EventKind::Access(AccessKind::Any), 0), RecentChange::None);
+    }
+
+    #[test]
+    fn test_list_directory() {
+        let temp = TempDir::new().unwrap();
+        fs::create_dir(temp.path().join("docs")).unwrap();
+        File::create(temp.path().join("docs").join("guide.md")).unwrap();
+        fs::create_dir(temp.path().join("empty")).unwrap();
+        File::create(temp.path().join("b.md")).unwrap();
+        File::create(temp.path().join("A.md")).unwrap();
+        File::create(temp.path().join("image.png")).unwrap();
+
+        let entries = list_directory(temp.path(), "/view/ws", "sub", &config::Config::default(), None).unwrap();
+        let names: Vec<&str> = entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["docs", "A.md", "b.md"]);
+        assert_eq!(entries[0].path, "/view/ws/sub/docs");
+        assert!(list_directory(&temp.path().join("missing"), "/view/ws", "", &config::Config::default(), None).is_err());
+    }
+
+    #[test]
+    fn test_list_directory_all_files() {
+        let temp = TempDir::new().unwrap();
+        fs::create_dir(temp.path().join("images")).unwrap();
+        File::create(temp.path().join("images").join("logo.png")).unwrap();
+        File::create(temp.path().join("guide.md")).unwrap();
+        File::create(temp.path().join("manual.pdf")).unwrap();
+
+        let config = config::Config::default();
+        let entries = list_directory(temp.path(), "/view/ws", "", &config, Some("/_raw/ws")).unwrap();
+        let urls: Vec<&str> = entries.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(urls, ["/view/ws/images", "/view/ws/guide.md", "/_raw/ws/manual.pdf"]);
+        assert!(entries[2].is_raw && !entries[1].is_raw);
+        assert!(ListingEntry::new("", entries[1].clone()).is_markdown);
+    }
+
+    fn entry(name: &str, is_dir: bool, size: u64, age: u64) -> FileEntry {
+        FileEntry {
+            name: name.to_string(),
+            path: String::new(),
+            is_dir,
+            is_raw: false,
+            size: String::new(),
+            modified: String::new(),
+            size_bytes: size,
+            modified_time: Some(std::time::UNIX_EPOCH + std::time::Duration::from_secs(1000 - age)),
+        }
+    }
+
+    #[test]
+    fn test_listing_query_sorts_and_filters() {
+        let listing = || vec![entry("b.md", false, 10, 1), entry("A.md", false, 30, 3), entry("docs", true, 0, 2), entry("c.md", false, 20, 2)];
+        let names = |entries: &[FileEntry]| entries.iter().map(|e| e.name.clone()).collect::<Vec<_>>();
+
+        let mut entries = listing();
+        ListingQuery::default().apply(&mut entries);
+        assert_eq!(names(&entries), ["docs", "A.md", "b.md", "c.md"]);
+
+        let mut entries = listing();
+        let query = ListingQuery { sort: SortKey::Size, order: SortOrder::Desc, ..Default::default() };
+        query.apply(&mut entries);
+        assert_eq!(names(&entries), ["docs", "A.md", "c.md", "b.md"]);
+
+        let mut entries = listing();
+        let query = ListingQuery { sort: SortKey::Modified, filter: " .MD".to_string(), ..Default::default() };
+        query.apply(&mut entries);
+        assert_eq!(names(&entries), ["A.md", "c.md", "b.md"]);
+    }
+
+    #[test]
+    fn test_listing_query_links() {
+        let query = ListingQuery::parse(Some("sort=size&order=desc&filter=a%20b&all=true"));
+        assert_eq!(query.carried(), "?sort=size&order=desc&all=true");
+        assert_eq!(SortHeader::new(&query, SortKey::Size).url, "?sort=size&order=asc&filter=a+b&all=true");
+        assert_eq!(SortHeader::new(&query, SortKey::Size).arrow, "\u{2193}");
+        assert_eq!(SortHeader::new(&query, SortKey::Name).url, "?sort=name&order=asc&filter=a+b&all=true");
+        assert_eq!(SortHeader::new(&query, SortKey::Modified).arrow, "");
+        assert_eq!(ListingQuery::default().carried(), "");
+    }
+
+    #[test]
+    fn test_listing_query_parse_is_lenient() {
+        let query = ListingQuery::parse(Some("sort=colour&order=up&format=xml&all=1&x=y"));
+        assert_eq!(query.sort, SortKey::Name);
+        assert_eq!(query.order, SortOrder::Asc);
+        assert_eq!(query.format, ListingFormat::Html);
+        assert!(query.all);
+        assert!(ListingQuery::parse(Some("all=on")).all);
+        assert!(!ListingQuery::parse(Some("all=false")).all);
+        assert_eq!(ListingQuery::parse(Some("format=json")).format, ListingFormat::Json);
+        assert_eq!(ListingQuery::parse(None).sort, SortKey::Name);
+        assert!(!ListingQuery::parse(Some("x=y")).explicit);
+        assert!(ListingQuery::parse(Some("filter=")).explicit);
+    }
+
+    #[test]
+    fn test_listing_entry_paths() {
+        let temp = TempDir::new().unwrap();
+        fs::create_dir(temp.path().join("guide")).unwrap();
+        File::create(temp.path().join("guide").join("intro.md")).unwrap();
+        File::create(temp.path().join("index.md")).unwrap();
+
+        let config = config::Config::default();
+        let entries: Vec<_> = list_directory(temp.path(), "/view/ws", "docs", &config, None)
+            .unwrap()
+            .into_iter()
+            .map(|entry| ListingEntry::new("docs", entry))
+            .collect();
+        assert_eq!(entries[0].path, "docs/guide");
+        assert!(entries[0].is_dir);
+        assert_eq!(entries[1].path, "docs/index.md");
+        assert_eq!(entries[1].url, "/view/ws/docs/index.md");
+
+        let root = ListingEntry::new("", list_directory(temp.path(), "/view/ws", "", &config, None).unwrap().remove(1));
+        assert_eq!(root.path, "index.md");
+    }
+
+    #[test]
+    fn test_render_markdown_respects_disabled_extension() {
+        let config = config::MarkdownConfig {
+            tables: false,
+            ..Default::default()
+        };
+        let html = render_markdown("| a |\n|---|\n| 1 |", &config, None);
+        assert!(!html.contains("<table>"));
     }
 }
@@ -0,0 +1,192 @@
+use axum::{
+    extract::{MatchedPath, Request},
+    http::{header, Method},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use prometheus::{
+    exponential_buckets, Encoder, Histogram, HistogramOpts, HistogramVec, IntCounter,
+    IntCounterVec, IntGauge, Opts, Registry, TextEncoder,
+};
+use std::{sync::LazyLock, time::Instant};
+
+/// Server metrics, exposed in Prometheus text format at `/metrics`.
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+pub struct Metrics {
+    registry: Registry,
+    pub http_requests: IntCounterVec,
+    pub http_request_duration: HistogramVec,
+    pub render_duration: Histogram,
+    pub workspaces: IntGauge,
+    pub watchers: IntGauge,
+    pub sse_clients: IntGauge,
+    pub ws_clients: IntGauge,
+    pub reload_events: IntCounter,
+    /// Broadcast messages a slow subscriber missed, by channel.
+    pub broadcast_lagged: IntCounterVec,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new_custom(Some("mdv".to_string()), None)
+            .expect("valid metrics prefix");
+
+        let http_requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests by route, method and status"),
+            &["route", "method", "status"],
+        )
+        .unwrap();
+        let http_request_duration = HistogramVec::new(
+            HistogramOpts::new("http_request_duration_seconds", "HTTP request latency by route"),
+            &["route", "method"],
+        )
+        .unwrap();
+        let render_duration = Histogram::with_opts(
+            HistogramOpts::new("render_duration_seconds", "Time to render a markdown document to HTML")
+                .buckets(exponential_buckets(0.0005, 2.0, 14).unwrap()),
+        )
+        .unwrap();
+        let workspaces = IntGauge::new("workspaces", "Registered workspaces").unwrap();
+        let watchers = IntGauge::new("watchers", "Running file watchers").unwrap();
+        let sse_clients = IntGauge::new("sse_clients", "Connected live-reload (SSE) clients").unwrap();
+        let ws_clients = IntGauge::new("websocket_clients", "Connected WebSocket clients").unwrap();
+        let reload_events =
+            IntCounter::new("reload_events_total", "Reload notifications sent by watchers").unwrap();
+        let broadcast_lagged = IntCounterVec::new(
+            Opts::new(
+                "broadcast_lagged_total",
+                "Broadcast messages dropped because a subscriber fell behind",
+            ),
+            &["channel"],
+        )
+        .unwrap();
+
+        registry.register(Box::new(http_requests.clone())).unwrap();
+        registry.register(Box::new(http_request_duration.clone())).unwrap();
+        registry.register(Box::new(render_duration.clone())).unwrap();
+        registry.register(Box::new(workspaces.clone())).unwrap();
+        registry.register(Box::new(watchers.clone())).unwrap();
+        registry.register(Box::new(sse_clients.clone())).unwrap();
+        registry.register(Box::new(ws_clients.clone())).unwrap();
+        registry.register(Box::new(reload_events.clone())).unwrap();
+        registry.register(Box::new(broadcast_lagged.clone())).unwrap();
+
+        Self {
+            registry,
+            http_requests,
+            http_request_duration,
+            render_duration,
+            workspaces,
+            watchers,
+            sse_clients,
+            ws_clients,
+            reload_events,
+            broadcast_lagged,
+        }
+    }
+
+    /// Encodes all metrics in the Prometheus text exposition format.
+    pub fn encode(&self) -> String {
+        let mut buffer = Vec::new();
+        let _ = TextEncoder::new().encode(&self.registry.gather(), &mut buffer);
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+}
+
+/// Decrements a gauge when dropped, so connection gauges stay correct
+/// however the connection ends.
+pub struct GaugeGuard(IntGauge);
+
+impl GaugeGuard {
+    pub fn new(gauge: &IntGauge) -> Self {
+        gauge.inc();
+        Self(gauge.clone())
+    }
+}
+
+impl Drop for GaugeGuard {
+    fn drop(&mut self) {
+        self.0.dec();
+    }
+}
+
+/// Label for a request method. Clients can send any token as the method,
+/// so non-standard ones are grouped under `other`.
+fn method_label(method: &Method) -> &'static str {
+    match *method {
+        Method::GET => "GET",
+        Method::HEAD => "HEAD",
+        Method::POST => "POST",
+        Method::PUT => "PUT",
+        Method::PATCH => "PATCH",
+        Method::DELETE => "DELETE",
+        Method::OPTIONS => "OPTIONS",
+        Method::CONNECT => "CONNECT",
+        Method::TRACE => "TRACE",
+        _ => "other",
+    }
+}
+
+/// Middleware recording request counts and latencies by matched route.
+/// Requests that match no route are grouped under `unmatched` to keep
+/// label cardinality bounded.
+pub async fn track_requests(req: Request, next: Next) -> Response {
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+    let method = method_label(req.method());
+    let start = Instant::now();
+
+    let response = next.run(req).await;
+
+    METRICS
+        .http_request_duration
+        .with_label_values(&[&route, method])
+        .observe(start.elapsed().as_secs_f64());
+    METRICS
+        .http_requests
+        .with_label_values(&[&route, method, response.status().as_str()])
+        .inc();
+    response
+}
+
+// Metrics endpoint
+pub async fn handle_metrics() -> Response {
+    (
+        [(header::CONTENT_TYPE, prometheus::TEXT_FORMAT)],
+        METRICS.encode(),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gauge_guard_restores_value() {
+        let gauge = IntGauge::new("test_gauge", "test").unwrap();
+        {
+            let _guard = GaugeGuard::new(&gauge);
+            assert_eq!(gauge.get(), 1);
+        }
+        assert_eq!(gauge.get(), 0);
+    }
+
+    #[test]
+    fn test_method_label() {
+        assert_eq!(method_label(&Method::PATCH), "PATCH");
+        assert_eq!(method_label(&Method::from_bytes(b"X-RANDOM-1234").unwrap()), "other");
+    }
+
+    #[test]
+    fn test_encode_contains_metrics() {
+        METRICS.reload_events.inc();
+        let text = METRICS.encode();
+        assert!(text.contains("mdv_reload_events_total"));
+        assert!(text.contains("mdv_workspaces"));
+    }
+}
@@ -0,0 +1,139 @@
+use std::{
+    collections::VecDeque,
+    path::Path,
+    sync::Mutex,
+    time::SystemTime,
+};
+
+/// Number of files kept in each list of a workspace.
+const CAPACITY: usize = 20;
+
+/// Recently viewed and recently changed files of one workspace, most
+/// recent first. Paths are relative to the workspace root and use `/`.
+#[derive(Default)]
+pub struct RecentFiles {
+    lists: Mutex<Lists>,
+}
+
+#[derive(Default)]
+struct Lists {
+    viewed: VecDeque<RecentEntry>,
+    changed: VecDeque<RecentEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecentEntry {
+    pub path: String,
+    pub time: SystemTime,
+}
+
+fn push(list: &mut VecDeque<RecentEntry>, path: String, time: SystemTime) {
+    list.retain(|entry| entry.path != path);
+    list.push_front(RecentEntry { path, time });
+    list.truncate(CAPACITY);
+}
+
+/// `path` relative to `root` with `/` separators, or `None` if it is not
+/// below `root`.
+pub fn relative_path(root: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(root).ok()?;
+    let parts: Vec<_> = relative.components().map(|c| c.as_os_str().to_string_lossy()).collect();
+    (!parts.is_empty()).then(|| parts.join("/"))
+}
+
+/// Merges the lists of several workspaces, most recent first, keeping
+/// at most `limit` entries.
+pub fn merge<T>(lists: impl IntoIterator<Item = (T, Vec<RecentEntry>)>, limit: usize) -> Vec<(T, RecentEntry)>
+where
+    T: Clone,
+{
+    let mut merged: Vec<(T, RecentEntry)> = lists
+        .into_iter()
+        .flat_map(|(key, entries)| entries.into_iter().map(move |entry| (key.clone(), entry)))
+        .collect();
+    merged.sort_by_key(|(_, entry)| std::cmp::Reverse(entry.time));
+    merged.truncate(limit);
+    merged
+}
+
+impl RecentFiles {
+    pub fn record_viewed(&self, path: String) {
+        push(&mut self.lists.lock().unwrap().viewed, path, SystemTime::now());
+    }
+
+    pub fn record_changed(&self, path: String) {
+        push(&mut self.lists.lock().unwrap().changed, path, SystemTime::now());
+    }
+
+    /// Drops a deleted file from both lists.
+    pub fn forget(&self, path: &str) {
+        let mut lists = self.lists.lock().unwrap();
+        lists.viewed.retain(|entry| entry.path != path);
+        lists.changed.retain(|entry| entry.path != path);
+    }
+
+    pub fn viewed(&self) -> Vec<RecentEntry> {
+        self.lists.lock().unwrap().viewed.iter().cloned().collect()
+    }
+
+    pub fn changed(&self) -> Vec<RecentEntry> {
+        self.lists.lock().unwrap().changed.iter().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(entries: &[RecentEntry]) -> Vec<&str> {
+        entries.iter().map(|e| e.path.as_str()).collect()
+    }
+
+    #[test]
+    fn test_most_recent_first_without_duplicates() {
+        let recent = RecentFiles::default();
+        recent.record_viewed("a.md".to_string());
+        recent.record_viewed("b.md".to_string());
+        recent.record_viewed("a.md".to_string());
+        recent.record_changed("c.md".to_string());
+
+        assert_eq!(paths(&recent.viewed()), ["a.md", "b.md"]);
+        assert_eq!(paths(&recent.changed()), ["c.md"]);
+
+        recent.forget("a.md");
+        assert_eq!(paths(&recent.viewed()), ["b.md"]);
+    }
+
+    #[test]
+    fn test_capacity() {
+        let recent = RecentFiles::default();
+        for i in 0..CAPACITY + 5 {
+            recent.record_changed(format!("{}.md", i));
+        }
+        let changed = recent.changed();
+        assert_eq!(changed.len(), CAPACITY);
+        assert_eq!(changed[0].path, format!("{}.md", CAPACITY + 4));
+    }
+
+    #[test]
+    fn test_merge() {
+        let a = RecentFiles::default();
+        let b = RecentFiles::default();
+        a.record_viewed("old.md".to_string());
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        b.record_viewed("new.md".to_string());
+        a.record_viewed("newest.md".to_string());
+
+        let merged = merge([("a", a.viewed()), ("b", b.viewed())], 2);
+        let merged: Vec<_> = merged.iter().map(|(key, e)| (*key, e.path.as_str())).collect();
+        assert_eq!(merged, [("a", "newest.md"), ("b", "new.md")]);
+    }
+
+    #[test]
+    fn test_relative_path() {
+        let root = Path::new("/repo");
+        assert_eq!(relative_path(root, Path::new("/repo/docs/a.md")).as_deref(), Some("docs/a.md"));
+        assert_eq!(relative_path(root, Path::new("/repo")), None);
+        assert_eq!(relative_path(root, Path::new("/other/a.md")), None);
+    }
+}
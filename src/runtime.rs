@@ -0,0 +1,317 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io,
+    path::{Path, PathBuf},
+};
+#[cfg(unix)]
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+
+/// Per-user directory for files describing running servers
+/// (`$XDG_RUNTIME_DIR/mdv`, falling back to the temp directory).
+pub fn runtime_dir() -> PathBuf {
+    match dirs::runtime_dir() {
+        Some(dir) => dir.join("mdv"),
+        None => std::env::temp_dir().join(format!("mdv-{}", whoami())),
+    }
+}
+
+fn whoami() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "user".to_string())
+}
+
+/// Name identifying a server instance by where it listens, so that
+/// servers on different ports or sockets keep separate runtime files.
+pub fn instance_name(tcp_port: Option<u16>, socket: Option<&Path>) -> String {
+    match (tcp_port, socket) {
+        (Some(port), _) => format!("port-{}", port),
+        (None, Some(socket)) => {
+            let sanitized: String = socket
+                .to_string_lossy()
+                .chars()
+                .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+                .collect();
+            format!("socket-{}", sanitized.trim_matches('_'))
+        }
+        (None, None) => "default".to_string(),
+    }
+}
+
+/// Contents of the runtime file a server writes once it is listening.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct RuntimeInfo {
+    pub pid: u32,
+    pub port: Option<u16>,
+    pub socket: Option<PathBuf>,
+    /// Bearer token for the control API (`/api/shutdown`).
+    pub token: String,
+}
+
+/// A registered workspace, saved when a server stops and registered again
+/// when the same instance starts.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct SavedWorkspace {
+    pub path: PathBuf,
+    pub name: String,
+    #[serde(default)]
+    pub alias: Option<String>,
+    pub trusted: bool,
+}
+
+/// Path of the JSON runtime file of an instance.
+pub fn info_path(dir: &Path, instance: &str) -> PathBuf {
+    dir.join(format!("{}.json", instance))
+}
+
+/// Path of the pidfile of an instance.
+pub fn pid_path(dir: &Path, instance: &str) -> PathBuf {
+    dir.join(format!("{}.pid", instance))
+}
+
+/// Path of the log file a daemonized instance writes to.
+pub fn log_path(dir: &Path, instance: &str) -> PathBuf {
+    dir.join(format!("{}.log", instance))
+}
+
+/// Path of the workspace registry an instance saves at shutdown.
+pub fn registry_path(dir: &Path, instance: &str) -> PathBuf {
+    dir.join(format!("{}.workspaces.json", instance))
+}
+
+fn lock_path(dir: &Path, instance: &str) -> PathBuf {
+    dir.join(format!("{}.lock", instance))
+}
+
+/// Takes the single-instance lock of `instance`. Returns `Ok(None)` when
+/// another process holds it. The lock lasts as long as the returned file
+/// stays open, and is released by the OS even if the process crashes.
+pub fn lock_instance(dir: &Path, instance: &str) -> io::Result<Option<File>> {
+    create_private_dir(dir)?;
+    let file = File::options()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(lock_path(dir, instance))?;
+    match file.try_lock() {
+        Ok(()) => Ok(Some(file)),
+        Err(std::fs::TryLockError::WouldBlock) => Ok(None),
+        Err(std::fs::TryLockError::Error(e)) => Err(e),
+    }
+}
+
+/// Writes the runtime file and pidfile of an instance.
+pub fn write_info(dir: &Path, instance: &str, info: &RuntimeInfo) -> io::Result<()> {
+    let json = serde_json::to_string_pretty(info).map_err(io::Error::other)?;
+    write_private_file(&info_path(dir, instance), &json)?;
+    write_private_file(&pid_path(dir, instance), &format!("{}\n", info.pid))
+}
+
+/// Reads the runtime file of an instance, if one has been written.
+pub fn read_info(dir: &Path, instance: &str) -> Option<RuntimeInfo> {
+    let json = std::fs::read_to_string(info_path(dir, instance)).ok()?;
+    serde_json::from_str(&json).ok()
+}
+
+/// Saves the workspace registry of an instance.
+pub fn write_registry(dir: &Path, instance: &str, workspaces: &[SavedWorkspace]) -> io::Result<()> {
+    let json = serde_json::to_string_pretty(workspaces).map_err(io::Error::other)?;
+    write_private_file(&registry_path(dir, instance), &json)
+}
+
+/// Reads the workspace registry an instance saved, if any.
+pub fn read_registry(dir: &Path, instance: &str) -> Vec<SavedWorkspace> {
+    std::fs::read_to_string(registry_path(dir, instance))
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+/// Removes the runtime file and pidfile of an instance. The lock file is
+/// left in place: deleting it could let two processes lock different files.
+pub fn remove_info(dir: &Path, instance: &str) {
+    let _ = std::fs::remove_file(info_path(dir, instance));
+    let _ = std::fs::remove_file(pid_path(dir, instance));
+}
+
+/// Describes the instance holding the lock, for "already running" errors.
+pub fn describe_running(dir: &Path, instance: &str) -> String {
+    match read_info(dir, instance) {
+        Some(info) => {
+            let mut at = Vec::new();
+            if let Some(port) = info.port {
+                at.push(format!("port {}", port));
+            }
+            if let Some(socket) = &info.socket {
+                at.push(format!("socket {}", socket.display()));
+            }
+            format!(
+                "mdv is already running (pid {}, {}); use `mdv status` or `mdv open` to reach it",
+                info.pid,
+                at.join(", ")
+            )
+        }
+        None => "another mdv server for this port/socket is starting up".to_string(),
+    }
+}
+
+/// Creates `dir` if needed and makes sure it can hold secrets: a real
+/// directory owned by the current user with mode 0700. The fallback
+/// runtime directory in `/tmp` has a predictable name, so one created by
+/// another user is refused rather than written into.
+pub fn create_private_dir(dir: &Path) -> io::Result<()> {
+    let mut builder = std::fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    builder.mode(0o700);
+    builder.create(dir)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+        let metadata = std::fs::symlink_metadata(dir)?;
+        // SAFETY: geteuid has no preconditions and cannot fail.
+        let uid = unsafe { libc::geteuid() };
+        if !metadata.file_type().is_dir() || metadata.uid() != uid {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("{} is not a directory owned by the current user", dir.display()),
+            ));
+        }
+        if metadata.mode() & 0o777 != 0o700 {
+            // Ours but too open, e.g. created by an older version.
+            std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700))?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes `contents` to `path`, readable by the owner only. A symlink at
+/// `path` is not followed.
+pub fn write_private_file(path: &Path, contents: &str) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        create_private_dir(parent)?;
+    }
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    options.mode(0o600).custom_flags(libc::O_NOFOLLOW);
+    let mut file = options.open(path)?;
+    #[cfg(unix)]
+    file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+    io::Write::write_all(&mut file, contents.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_instance_name() {
+        assert_eq!(instance_name(Some(3000), None), "port-3000");
+        assert_eq!(instance_name(Some(3000), Some(Path::new("/tmp/mdv.sock"))), "port-3000");
+        assert_eq!(instance_name(None, Some(Path::new("/tmp/mdv.sock"))), "socket-tmp_mdv_sock");
+    }
+
+    #[test]
+    fn test_info_roundtrip() {
+        let temp = TempDir::new().unwrap();
+        let dir = temp.path().join("runtime");
+        let info = RuntimeInfo {
+            pid: 42,
+            port: Some(3000),
+            socket: None,
+            token: "secret".to_string(),
+        };
+
+        assert_eq!(read_info(&dir, "port-3000"), None);
+        write_info(&dir, "port-3000", &info).unwrap();
+        assert_eq!(read_info(&dir, "port-3000"), Some(info));
+        assert_eq!(
+            std::fs::read_to_string(pid_path(&dir, "port-3000")).unwrap(),
+            "42\n"
+        );
+
+        remove_info(&dir, "port-3000");
+        assert_eq!(read_info(&dir, "port-3000"), None);
+        assert!(!pid_path(&dir, "port-3000").exists());
+    }
+
+    #[test]
+    fn test_registry_roundtrip() {
+        let temp = TempDir::new().unwrap();
+        let workspaces = vec![SavedWorkspace {
+            path: PathBuf::from("/home/user/notes"),
+            name: "Notes".to_string(),
+            alias: Some("notes".to_string()),
+            trusted: true,
+        }];
+
+        assert!(read_registry(temp.path(), "port-3000").is_empty());
+        write_registry(temp.path(), "port-3000", &workspaces).unwrap();
+        assert_eq!(read_registry(temp.path(), "port-3000"), workspaces);
+        assert!(read_registry(temp.path(), "port-4000").is_empty());
+
+        std::fs::write(registry_path(temp.path(), "port-3000"), "garbage").unwrap();
+        assert!(read_registry(temp.path(), "port-3000").is_empty());
+    }
+
+    #[test]
+    fn test_lock_instance_is_exclusive() {
+        let temp = TempDir::new().unwrap();
+
+        let lock = lock_instance(temp.path(), "port-3000").unwrap();
+        assert!(lock.is_some());
+        assert!(lock_instance(temp.path(), "port-3000").unwrap().is_none());
+        assert!(lock_instance(temp.path(), "port-4000").unwrap().is_some());
+
+        drop(lock);
+        assert!(lock_instance(temp.path(), "port-3000").unwrap().is_some());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_private_file_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("runtime/x.json");
+
+        write_private_file(&path, "secret").unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_private_file_refuses_symlink() {
+        let temp = TempDir::new().unwrap();
+        let target = temp.path().join("target");
+        let link = temp.path().join("runtime/x.json");
+        std::fs::write(&target, "").unwrap();
+        create_private_dir(link.parent().unwrap()).unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        assert!(write_private_file(&link, "secret").is_err());
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_create_private_dir_checks_existing() {
+        use std::os::unix::fs::PermissionsExt;
+        let temp = TempDir::new().unwrap();
+        let dir = temp.path().join("runtime");
+        std::fs::create_dir(&dir).unwrap();
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        create_private_dir(&dir).unwrap();
+        assert_eq!(std::fs::metadata(&dir).unwrap().permissions().mode() & 0o777, 0o700);
+
+        let link = temp.path().join("link");
+        std::os::unix::fs::symlink(&dir, &link).unwrap();
+        assert!(create_private_dir(&link).is_err());
+    }
+}
@@ -0,0 +1,104 @@
+use std::{borrow::Cow, sync::LazyLock};
+
+/// Prepended to every `id` in untrusted markdown, as GitHub does, so it
+/// cannot clobber elements or globals the page scripts rely on.
+pub const ID_PREFIX: &str = "user-content-";
+
+/// GitHub-like allow-list for raw HTML embedded in markdown.
+/// Scripts, event handlers, `style` and non-http(s) URLs are removed, while
+/// the markup produced by pulldown-cmark (code languages, task list
+/// checkboxes, footnotes) is kept intact.
+static SANITIZER: LazyLock<ammonia::Builder<'static>> = LazyLock::new(|| {
+    let mut builder = ammonia::Builder::default();
+    builder
+        .add_tags(&["input"])
+        .add_tag_attributes("input", &["type", "checked", "disabled"])
+        .add_tag_attributes("code", &["class"])
+        .add_tag_attributes("pre", &["class"])
+        .add_tag_attributes("sup", &["class"])
+        .add_tag_attributes("div", &["class", "align"])
+        .add_tag_attributes("p", &["align"])
+        .add_tag_attributes("img", &["align"])
+        .add_generic_attributes(&["id"])
+        .id_prefix(Some(ID_PREFIX))
+        .attribute_filter(|element, attribute, value| {
+            match (element, attribute) {
+                ("input", "type") if value != "checkbox" => None,
+                (_, "class") if !is_allowed_class(value) => None,
+                // In-page links follow the prefixed ids.
+                ("a", "href") if value.len() > 1 && value.starts_with('#') => {
+                    Some(Cow::Owned(format!("#{}{}", ID_PREFIX, &value[1..])))
+                }
+                _ => Some(Cow::Borrowed(value)),
+            }
+        });
+    builder
+});
+
+fn is_allowed_class(value: &str) -> bool {
+    value
+        .split_whitespace()
+        .all(|class| class.starts_with("language-") || class.starts_with("footnote-"))
+}
+
+/// Removes disallowed tags and attributes from rendered markdown HTML.
+pub fn sanitize_html(html: &str) -> String {
+    SANITIZER.clean(html).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_strips_script() {
+        let html = sanitize_html("<p>hi</p><script>alert(1)</script>");
+        assert!(html.contains("<p>hi</p>"));
+        assert!(!html.contains("<script"));
+        assert!(!html.contains("alert"));
+    }
+
+    #[test]
+    fn test_sanitize_strips_event_handlers() {
+        let html = sanitize_html(r#"<img src="x.png" onerror="alert(1)"><a href="/a" onclick="x()">a</a>"#);
+        assert!(html.contains(r#"src="x.png""#));
+        assert!(!html.contains("onerror"));
+        assert!(!html.contains("onclick"));
+    }
+
+    #[test]
+    fn test_sanitize_strips_javascript_urls() {
+        let html = sanitize_html(r#"<a href="javascript:alert(1)">x</a>"#);
+        assert!(!html.contains("javascript:"));
+    }
+
+    #[test]
+    fn test_sanitize_keeps_code_language() {
+        let html = sanitize_html(r#"<pre><code class="language-rust">fn main() {}</code></pre>"#);
+        assert!(html.contains(r#"class="language-rust""#));
+    }
+
+    #[test]
+    fn test_sanitize_strips_unknown_classes() {
+        let html = sanitize_html(r#"<div class="fixed inset-0">overlay</div>"#);
+        assert!(!html.contains("fixed"));
+    }
+
+    #[test]
+    fn test_sanitize_prefixes_ids() {
+        let html = sanitize_html(r##"<h2 id="tree">t</h2><a href="#tree">t</a><a href="#">top</a>"##);
+        assert!(html.contains(r#"id="user-content-tree""#));
+        assert!(html.contains(r##"href="#user-content-tree""##));
+        assert!(html.contains(r##"href="#""##));
+    }
+
+    #[test]
+    fn test_sanitize_keeps_tasklist_checkbox() {
+        let html = sanitize_html(r#"<ul><li><input disabled="" type="checkbox" checked=""/> done</li></ul>"#);
+        assert!(html.contains("checkbox"));
+        assert!(html.contains("checked"));
+
+        let html = sanitize_html(r#"<input type="text" value="x">"#);
+        assert!(!html.contains("text"));
+    }
+}
@@ -0,0 +1,420 @@
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use std::{
+    net::IpAddr,
+    path::{Path, PathBuf},
+};
+
+use crate::{json_error, AppState};
+
+/// Host/Origin allow-list used to reject DNS-rebinding and cross-site requests.
+pub struct OriginPolicy {
+    allowed_hosts: Vec<String>,
+    allowed_origins: Vec<String>,
+}
+
+impl OriginPolicy {
+    /// Builds a policy for a server bound to `bind_host`.
+    /// Loopback names and IP literals are always accepted as `Host`; the
+    /// hosts of `extra_origins` are accepted as well so that LAN names work.
+    pub fn new(bind_host: &str, extra_origins: &[String]) -> Self {
+        let mut allowed_hosts = vec!["localhost".to_string()];
+        if bind_host.parse::<IpAddr>().is_err() {
+            allowed_hosts.push(bind_host.to_ascii_lowercase());
+        }
+
+        let allowed_origins: Vec<String> = extra_origins
+            .iter()
+            .map(|o| normalize_origin(o))
+            .collect();
+
+        for origin in &allowed_origins {
+            if let Some((_, authority)) = origin.split_once("://") {
+                let host = split_host_port(authority).0;
+                if !allowed_hosts.iter().any(|h| h == host) {
+                    allowed_hosts.push(host.to_string());
+                }
+            }
+        }
+
+        Self {
+            allowed_hosts,
+            allowed_origins,
+        }
+    }
+
+    /// Returns true if the `Host` header value names this server.
+    pub fn is_host_allowed(&self, host_header: &str) -> bool {
+        let host = host_header.trim().to_ascii_lowercase();
+        let name = split_host_port(&host).0;
+        if name.is_empty() {
+            return false;
+        }
+        if name.trim_matches(|c| c == '[' || c == ']').parse::<IpAddr>().is_ok() {
+            return true;
+        }
+        self.allowed_hosts.iter().any(|h| h == name)
+    }
+
+    /// Returns true if a request carrying `origin` may be served.
+    /// Same-origin requests (origin authority equals `Host`) and configured
+    /// extra origins are accepted; opaque (`null`) origins never are.
+    pub fn is_origin_allowed(&self, origin: &str, host_header: &str) -> bool {
+        let origin = normalize_origin(origin);
+        if origin == "null" {
+            return false;
+        }
+        if self.allowed_origins.contains(&origin) {
+            return true;
+        }
+        match origin.split_once("://") {
+            Some((scheme, authority)) => {
+                let host = host_header.trim().to_ascii_lowercase();
+                (scheme == "http" || scheme == "https") && authority == host
+            }
+            None => false,
+        }
+    }
+}
+
+fn normalize_origin(origin: &str) -> String {
+    origin.trim().trim_end_matches('/').to_ascii_lowercase()
+}
+
+/// Splits `host[:port]` (with bracketed IPv6 support) into its parts.
+fn split_host_port(authority: &str) -> (&str, Option<&str>) {
+    if authority.starts_with('[') {
+        if let Some(end) = authority.find(']') {
+            let port = authority[end + 1..].strip_prefix(':');
+            return (&authority[..=end], port);
+        }
+        return (authority, None);
+    }
+    match authority.rsplit_once(':') {
+        Some((host, port)) if !host.contains(':') => (host, Some(port)),
+        _ => (authority, None),
+    }
+}
+
+/// Rejects subresource requests that the browser marks as cross-site.
+/// Top-level navigations are still allowed so that links into mdv work.
+fn is_cross_site_subresource(headers: &HeaderMap) -> bool {
+    let site = headers.get("sec-fetch-site").and_then(|v| v.to_str().ok());
+    let mode = headers.get("sec-fetch-mode").and_then(|v| v.to_str().ok());
+    site == Some("cross-site") && mode != Some("navigate")
+}
+
+/// Middleware enforcing the Host/Origin allow-list on every route,
+/// including WebSocket upgrades on `/ws`.
+pub async fn enforce_origin_policy(
+    State(state): State<AppState>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let headers = req.headers();
+    let policy = &state.origin_policy;
+
+    let host = headers
+        .get(header::HOST)
+        .and_then(|v| v.to_str().ok())
+        .or_else(|| req.uri().authority().map(|a| a.as_str()))
+        .unwrap_or("");
+    if !policy.is_host_allowed(host) {
+        tracing::warn!(host, "Rejected request with disallowed Host");
+        return json_error(StatusCode::FORBIDDEN, "Host not allowed");
+    }
+
+    if let Some(origin) = headers.get(header::ORIGIN) {
+        let origin = origin.to_str().unwrap_or("null");
+        if !policy.is_origin_allowed(origin, host) {
+            tracing::warn!(origin, "Rejected request with disallowed Origin");
+            return json_error(StatusCode::FORBIDDEN, "Origin not allowed");
+        }
+    }
+
+    if is_cross_site_subresource(headers) {
+        tracing::warn!("Rejected cross-site subresource request");
+        return json_error(StatusCode::FORBIDDEN, "Cross-site request rejected");
+    }
+
+    next.run(req).await
+}
+
+/// CSP for raw workspace files that can run scripts. `sandbox` gives the
+/// document an opaque origin, so HTML or SVG opened directly cannot script
+/// the mdv APIs.
+pub const RAW_FILE_CSP: &str =
+    "sandbox; default-src 'none'; img-src 'self' data:; style-src 'unsafe-inline'; media-src 'self'";
+
+/// Media types browsers run scripts in when opened as a document. Only
+/// these get `RAW_FILE_CSP`: a sandboxed PDF is not rendered at all.
+pub fn is_active_content(mime: &mime_guess::Mime) -> bool {
+    matches!(
+        mime.essence_str(),
+        "text/html" | "application/xhtml+xml" | "image/svg+xml" | "text/xml" | "application/xml"
+    )
+}
+
+fn random_hex<const N: usize>() -> String {
+    let mut bytes = [0u8; N];
+    getrandom::fill(&mut bytes).expect("failed to read system randomness");
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Returns a fresh random nonce for inline `<script>` tags.
+pub fn generate_nonce() -> String {
+    random_hex::<16>()
+}
+
+/// Returns a new secret for authenticating control API calls.
+pub fn generate_token() -> String {
+    random_hex::<32>()
+}
+
+/// Returns true if the request carries `Authorization: Bearer <token>`.
+pub fn has_bearer_token(headers: &HeaderMap, token: &str) -> bool {
+    let Some(provided) = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+    else {
+        return false;
+    };
+    constant_time_eq(provided.trim().as_bytes(), token.as_bytes())
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// CSP for rendered pages, allowing only the nonce'd inline scripts and
+/// the CDN assets bundled in the templates.
+pub fn page_csp(nonce: &str) -> String {
+    format!(
+        "default-src 'none'; \
+         script-src 'nonce-{}' https://cdn.tailwindcss.com https://cdnjs.cloudflare.com https://cdn.jsdelivr.net; \
+         style-src 'self' 'unsafe-inline' https://cdnjs.cloudflare.com; \
+         img-src 'self' data: https:; \
+         font-src 'self' data: https://cdnjs.cloudflare.com; \
+         media-src 'self'; \
+         connect-src 'self'; \
+         object-src 'none'; \
+         base-uri 'none'; \
+         form-action 'self'; \
+         frame-ancestors 'none'",
+        nonce
+    )
+}
+
+/// Middleware adding headers that apply to every response.
+pub async fn set_security_headers(req: Request, next: Next) -> Response {
+    let mut response = next.run(req).await;
+    let headers = response.headers_mut();
+    headers.insert(header::X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+    headers.insert(header::X_FRAME_OPTIONS, HeaderValue::from_static("DENY"));
+    headers.insert(header::REFERRER_POLICY, HeaderValue::from_static("same-origin"));
+    response
+}
+
+/// Directories under the home directory that are never registrable.
+const DEFAULT_DENIED_HOME_DIRS: &[&str] = &[".ssh", ".gnupg", ".aws"];
+
+/// Controls which directories may be registered as workspaces.
+pub struct RegisterPolicy {
+    allowed_roots: Vec<PathBuf>,
+    denied_roots: Vec<PathBuf>,
+}
+
+impl RegisterPolicy {
+    /// Builds a policy from the configured roots.
+    /// An empty `allowed_roots` falls back to the user's home directory, and
+    /// `denied_roots` always extends the built-in credential directories.
+    pub fn new(allowed_roots: &[PathBuf], denied_roots: &[PathBuf]) -> Self {
+        let home = std::env::home_dir();
+
+        let mut allowed: Vec<PathBuf> = if allowed_roots.is_empty() {
+            home.iter().cloned().collect()
+        } else {
+            allowed_roots.to_vec()
+        };
+
+        let mut denied: Vec<PathBuf> = home
+            .iter()
+            .flat_map(|h| DEFAULT_DENIED_HOME_DIRS.iter().map(move |d| h.join(d)))
+            .collect();
+        denied.extend(denied_roots.iter().cloned());
+
+        for path in allowed.iter_mut().chain(denied.iter_mut()) {
+            if let Ok(canonical) = path.canonicalize() {
+                *path = canonical;
+            }
+        }
+
+        Self {
+            allowed_roots: allowed,
+            denied_roots: denied,
+        }
+    }
+
+    /// Checks whether a canonical directory may be registered.
+    pub fn check(&self, path: &Path) -> Result<(), &'static str> {
+        if self.is_denied(path) {
+            return Err("Path is in a denied location");
+        }
+        if !self.allowed_roots.iter().any(|root| path.starts_with(root)) {
+            return Err("Path is outside the allowed workspace roots");
+        }
+        Ok(())
+    }
+
+    /// Returns true if a canonical path lies inside a denied root.
+    /// Also used when serving files so that a registered ancestor (such as
+    /// the home directory) does not expose denied subdirectories.
+    pub fn is_denied(&self, path: &Path) -> bool {
+        self.denied_roots.iter().any(|root| path.starts_with(root))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn policy() -> OriginPolicy {
+        OriginPolicy::new("127.0.0.1", &[])
+    }
+
+    #[test]
+    fn test_host_allowed_loopback() {
+        let p = policy();
+        assert!(p.is_host_allowed("localhost:3000"));
+        assert!(p.is_host_allowed("127.0.0.1:3000"));
+        assert!(p.is_host_allowed("[::1]:3000"));
+        assert!(p.is_host_allowed("LOCALHOST"));
+    }
+
+    #[test]
+    fn test_host_rejects_rebinding_domain() {
+        let p = policy();
+        assert!(!p.is_host_allowed("evil.example.com:3000"));
+        assert!(!p.is_host_allowed(""));
+    }
+
+    #[test]
+    fn test_host_allowed_from_extra_origin() {
+        let p = OriginPolicy::new("0.0.0.0", &["http://devbox.local:3000/".to_string()]);
+        assert!(p.is_host_allowed("devbox.local:3000"));
+        assert!(!p.is_host_allowed("other.local:3000"));
+    }
+
+    #[test]
+    fn test_origin_same_origin_allowed() {
+        let p = policy();
+        assert!(p.is_origin_allowed("http://localhost:3000", "localhost:3000"));
+        assert!(p.is_origin_allowed("https://127.0.0.1:3000", "127.0.0.1:3000"));
+    }
+
+    #[test]
+    fn test_origin_cross_site_rejected() {
+        let p = policy();
+        assert!(!p.is_origin_allowed("https://evil.example.com", "localhost:3000"));
+        assert!(!p.is_origin_allowed("http://localhost:4000", "localhost:3000"));
+        assert!(!p.is_origin_allowed("null", "localhost:3000"));
+    }
+
+    #[test]
+    fn test_origin_extra_allowed() {
+        let p = OriginPolicy::new("127.0.0.1", &["https://Docs.Example.com".to_string()]);
+        assert!(p.is_origin_allowed("https://docs.example.com", "localhost:3000"));
+    }
+
+    #[test]
+    fn test_cross_site_subresource() {
+        let mut headers = HeaderMap::new();
+        headers.insert("sec-fetch-site", HeaderValue::from_static("cross-site"));
+        headers.insert("sec-fetch-mode", HeaderValue::from_static("no-cors"));
+        assert!(is_cross_site_subresource(&headers));
+
+        headers.insert("sec-fetch-mode", HeaderValue::from_static("navigate"));
+        assert!(!is_cross_site_subresource(&headers));
+
+        headers.insert("sec-fetch-site", HeaderValue::from_static("same-origin"));
+        headers.insert("sec-fetch-mode", HeaderValue::from_static("cors"));
+        assert!(!is_cross_site_subresource(&headers));
+    }
+
+    #[test]
+    fn test_generate_nonce() {
+        let a = generate_nonce();
+        let b = generate_nonce();
+        assert_eq!(a.len(), 32);
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn test_has_bearer_token() {
+        let token = generate_token();
+        assert_eq!(token.len(), 64);
+
+        let mut headers = HeaderMap::new();
+        assert!(!has_bearer_token(&headers, &token));
+
+        headers.insert(header::AUTHORIZATION, HeaderValue::from_str(&format!("Bearer {}", token)).unwrap());
+        assert!(has_bearer_token(&headers, &token));
+
+        headers.insert(header::AUTHORIZATION, HeaderValue::from_static("Bearer wrong"));
+        assert!(!has_bearer_token(&headers, &token));
+    }
+
+    #[test]
+    fn test_page_csp_contains_nonce() {
+        let csp = page_csp("abc123");
+        assert!(csp.contains("'nonce-abc123'"));
+        assert!(csp.contains("object-src 'none'"));
+        assert!(!csp.contains("unsafe-eval"));
+    }
+
+    fn register_policy(temp: &TempDir) -> RegisterPolicy {
+        let allowed = temp.path().join("allowed");
+        let denied = allowed.join("secrets");
+        fs::create_dir_all(&denied).unwrap();
+        RegisterPolicy::new(&[allowed], &[denied])
+    }
+
+    #[test]
+    fn test_register_policy_allows_inside_root() {
+        let temp = TempDir::new().unwrap();
+        let policy = register_policy(&temp);
+        let project = temp.path().join("allowed/project");
+        fs::create_dir(&project).unwrap();
+
+        assert!(policy.check(&project.canonicalize().unwrap()).is_ok());
+    }
+
+    #[test]
+    fn test_register_policy_rejects_outside_root() {
+        let temp = TempDir::new().unwrap();
+        let policy = register_policy(&temp);
+
+        assert!(policy.check(&temp.path().canonicalize().unwrap()).is_err());
+        assert!(policy.check(Path::new("/")).is_err());
+    }
+
+    #[test]
+    fn test_register_policy_rejects_denied() {
+        let temp = TempDir::new().unwrap();
+        let policy = register_policy(&temp);
+        let inner = temp.path().join("allowed/secrets/keys");
+        fs::create_dir(&inner).unwrap();
+
+        let canonical = inner.canonicalize().unwrap();
+        assert_eq!(policy.check(&canonical), Err("Path is in a denied location"));
+        assert!(policy.is_denied(&canonical));
+    }
+}
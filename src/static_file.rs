@@ -0,0 +1,228 @@
+use axum::{
+    body::Body,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{Html, IntoResponse, Response},
+};
+use std::{io::SeekFrom, ops::RangeInclusive, path::Path};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio_util::io::ReaderStream;
+
+use crate::{http_cache::Validators, security};
+
+/// A `Range` request header evaluated against a file length.
+#[derive(Debug, PartialEq)]
+enum ByteRange {
+    /// No usable range: serve the whole file.
+    Full,
+    Partial(RangeInclusive<u64>),
+    Unsatisfiable,
+}
+
+/// Parses a single `bytes=` range (RFC 9110, section 14.1.2). Multiple
+/// ranges and other units are ignored, which the RFC allows.
+fn parse_range(value: &str, len: u64) -> ByteRange {
+    let Some(spec) = value.trim().strip_prefix("bytes=") else {
+        return ByteRange::Full;
+    };
+    if spec.contains(',') {
+        return ByteRange::Full;
+    }
+    let Some((start, end)) = spec.trim().split_once('-') else {
+        return ByteRange::Full;
+    };
+
+    let range = match (start.trim(), end.trim()) {
+        ("", "") => return ByteRange::Full,
+        ("", suffix) => match suffix.parse::<u64>() {
+            Ok(0) => return ByteRange::Unsatisfiable,
+            Ok(n) => len.saturating_sub(n)..=len.saturating_sub(1),
+            Err(_) => return ByteRange::Full,
+        },
+        (start, end) => {
+            let Ok(start) = start.parse::<u64>() else { return ByteRange::Full };
+            let end = match end {
+                "" => len.saturating_sub(1),
+                end => match end.parse::<u64>() {
+                    Ok(end) if end >= start => end.min(len.saturating_sub(1)),
+                    _ => return ByteRange::Full,
+                },
+            };
+            start..=end
+        }
+    };
+
+    if len == 0 || *range.start() >= len {
+        ByteRange::Unsatisfiable
+    } else {
+        ByteRange::Partial(range)
+    }
+}
+
+/// Serves a workspace file without loading it into memory, honouring
+/// conditional requests and single byte ranges.
+pub async fn serve(full_path: &Path, headers: &HeaderMap) -> Response {
+    let mime = mime_guess::from_path(full_path).first_or_octet_stream();
+    serve_as(full_path, headers, mime).await
+}
+
+/// Like `serve`, but as `text/plain` whatever the extension. Browsers
+/// download `text/markdown` instead of displaying it.
+pub async fn serve_plain_text(full_path: &Path, headers: &HeaderMap) -> Response {
+    serve_as(full_path, headers, mime_guess::mime::TEXT_PLAIN).await
+}
+
+async fn serve_as(full_path: &Path, headers: &HeaderMap, mime: mime_guess::Mime) -> Response {
+    let failed = || (StatusCode::INTERNAL_SERVER_ERROR, Html("Failed to read file")).into_response();
+
+    let Ok(mut file) = tokio::fs::File::open(full_path).await else {
+        return failed();
+    };
+    let Ok(metadata) = file.metadata().await else {
+        return failed();
+    };
+    let validators = Validators::for_file(&metadata);
+    if validators.is_not_modified(headers) {
+        return validators.not_modified();
+    }
+
+    let len = metadata.len();
+    let range = match headers.get(header::RANGE).and_then(|v| v.to_str().ok()) {
+        Some(value) if validators.if_range_matches(headers) => parse_range(value, len),
+        _ => ByteRange::Full,
+    };
+
+    let content_type = if mime.type_() == "text" {
+        format!("{}; charset=utf-8", mime)
+    } else {
+        mime.to_string()
+    };
+
+    let (status, body, body_len, content_range) = match range {
+        ByteRange::Full => (StatusCode::OK, Body::from_stream(ReaderStream::new(file)), len, None),
+        ByteRange::Partial(range) => {
+            if file.seek(SeekFrom::Start(*range.start())).await.is_err() {
+                return failed();
+            }
+            let count = range.end() - range.start() + 1;
+            let stream = ReaderStream::new(file.take(count));
+            let content_range = format!("bytes {}-{}/{}", range.start(), range.end(), len);
+            (StatusCode::PARTIAL_CONTENT, Body::from_stream(stream), count, Some(content_range))
+        }
+        ByteRange::Unsatisfiable => {
+            return (
+                StatusCode::RANGE_NOT_SATISFIABLE,
+                [(header::CONTENT_RANGE, format!("bytes */{}", len))],
+            )
+                .into_response();
+        }
+    };
+
+    let mut response = (status, [(header::CONTENT_TYPE, content_type)], body).into_response();
+    let response_headers = response.headers_mut();
+    if security::is_active_content(&mime) {
+        response_headers.insert(header::CONTENT_SECURITY_POLICY, HeaderValue::from_static(security::RAW_FILE_CSP));
+    }
+    response_headers.insert(header::CONTENT_LENGTH, HeaderValue::from(body_len));
+    response_headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    if let Some(content_range) = content_range.and_then(|v| HeaderValue::from_str(&v).ok()) {
+        response_headers.insert(header::CONTENT_RANGE, content_range);
+    }
+    validators.apply(&mut response);
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http_body_util::BodyExt;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range("bytes=0-4", 10), ByteRange::Partial(0..=4));
+        assert_eq!(parse_range("bytes=5-", 10), ByteRange::Partial(5..=9));
+        assert_eq!(parse_range("bytes=-3", 10), ByteRange::Partial(7..=9));
+        assert_eq!(parse_range("bytes=-30", 10), ByteRange::Partial(0..=9));
+        assert_eq!(parse_range("bytes=8-100", 10), ByteRange::Partial(8..=9));
+        assert_eq!(parse_range("bytes=10-", 10), ByteRange::Unsatisfiable);
+        assert_eq!(parse_range("bytes=-0", 10), ByteRange::Unsatisfiable);
+        assert_eq!(parse_range("bytes=0-", 0), ByteRange::Unsatisfiable);
+        assert_eq!(parse_range("bytes=5-2", 10), ByteRange::Full);
+        assert_eq!(parse_range("bytes=0-1,4-5", 10), ByteRange::Full);
+        assert_eq!(parse_range("items=0-1", 10), ByteRange::Full);
+        assert_eq!(parse_range("bytes=x-1", 10), ByteRange::Full);
+    }
+
+    async fn body_text(response: Response) -> String {
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_serve_full_and_partial() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("data.txt");
+        std::fs::write(&path, "0123456789").unwrap();
+
+        let response = serve(&path, &HeaderMap::new()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::ACCEPT_RANGES], "bytes");
+        assert_eq!(body_text(response).await, "0123456789");
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::RANGE, HeaderValue::from_static("bytes=2-5"));
+        let response = serve(&path, &headers).await;
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers()[header::CONTENT_RANGE], "bytes 2-5/10");
+        assert_eq!(response.headers()[header::CONTENT_LENGTH], "4");
+        assert_eq!(body_text(response).await, "2345");
+    }
+
+    #[tokio::test]
+    async fn test_serve_sandboxes_only_active_content() {
+        let temp = TempDir::new().unwrap();
+        let pdf = temp.path().join("spec.pdf");
+        let html = temp.path().join("page.html");
+        std::fs::write(&pdf, "%PDF-1.4").unwrap();
+        std::fs::write(&html, "<script>alert(1)</script>").unwrap();
+
+        let response = serve(&pdf, &HeaderMap::new()).await;
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/pdf");
+        assert!(response.headers().get(header::CONTENT_SECURITY_POLICY).is_none());
+
+        let response = serve(&html, &HeaderMap::new()).await;
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "text/html; charset=utf-8");
+        assert_eq!(response.headers()[header::CONTENT_SECURITY_POLICY], security::RAW_FILE_CSP);
+    }
+
+    #[tokio::test]
+    async fn test_serve_plain_text() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("huge.md");
+        std::fs::write(&path, "# big").unwrap();
+
+        let response = serve(&path, &HeaderMap::new()).await;
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "text/markdown; charset=utf-8");
+        let response = serve_plain_text(&path, &HeaderMap::new()).await;
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "text/plain; charset=utf-8");
+    }
+
+    #[tokio::test]
+    async fn test_serve_unsatisfiable_and_stale_if_range() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("data.txt");
+        std::fs::write(&path, "0123456789").unwrap();
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::RANGE, HeaderValue::from_static("bytes=20-"));
+        let response = serve(&path, &headers).await;
+        assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(response.headers()[header::CONTENT_RANGE], "bytes */10");
+
+        headers.insert(header::RANGE, HeaderValue::from_static("bytes=0-1"));
+        headers.insert(header::IF_RANGE, HeaderValue::from_static("\"stale\""));
+        let response = serve(&path, &headers).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body_text(response).await, "0123456789");
+    }
+}
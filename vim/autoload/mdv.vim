@@ -18,6 +18,21 @@ if !exists('g:mdv_auto_open_browser')
   let g:mdv_auto_open_browser = 1
 endif
 
+" Serve and connect over HTTPS
+if !exists('g:mdv_tls')
+  let g:mdv_tls = 0
+endif
+
+" CA/certificate file used to verify HTTPS (empty: skip verification)
+if !exists('g:mdv_cacert')
+  let g:mdv_cacert = ''
+endif
+
+" Unix domain socket used for API calls (empty: use host/port)
+if !exists('g:mdv_socket')
+  let g:mdv_socket = ''
+endif
+
 " Internal state
 let s:mdv_job = v:null
 let s:registered_workspaces = {}
@@ -25,7 +40,28 @@ let s:workspace_cache = []
 
 " Get base URL
 function! s:base_url() abort
-  return 'http://' . g:mdv_host . ':' . g:mdv_port
+  return (g:mdv_tls ? 'https://' : 'http://') . g:mdv_host . ':' . g:mdv_port
+endfunction
+
+" Get base URL for API calls (the host is ignored over a Unix socket)
+function! s:api_url() abort
+  return empty(g:mdv_socket) ? s:base_url() : 'http://localhost'
+endfunction
+
+" Build curl command prefix (as string) honoring g:mdv_socket and g:mdv_tls
+function! s:curl_cmd() abort
+  return join(map(s:curl_list(), 'shellescape(v:val)'), ' ') . ' '
+endfunction
+
+" Build curl command prefix (as list) honoring g:mdv_socket and g:mdv_tls
+function! s:curl_list() abort
+  let l:cmd = ['curl', '-s']
+  if !empty(g:mdv_socket)
+    call extend(l:cmd, ['--unix-socket', g:mdv_socket])
+  elseif g:mdv_tls
+    call extend(l:cmd, empty(g:mdv_cacert) ? ['--insecure'] : ['--cacert', g:mdv_cacert])
+  endif
+  return l:cmd
 endfunction
 
 " Run command asynchronously (detached)
@@ -52,8 +88,8 @@ endfunction
 
 " Check if server is running
 function! mdv#is_running() abort
-  let l:url = s:base_url() . '/api/status'
-  let l:result = system('curl -s -o /dev/null -w "%{http_code}" --max-time 1 ' . shellescape(l:url))
+  let l:url = s:api_url() . '/api/status'
+  let l:result = system(s:curl_cmd() . '-o /dev/null -w "%{http_code}" --max-time 1 ' . shellescape(l:url))
   let l:running = l:result ==# '200'
   if !l:running
     call s:clear_workspace_cache()
@@ -61,6 +97,18 @@ function! mdv#is_running() abort
   return l:running
 endfunction
 
+" Command line for the mdv binary with the configured server options
+function! s:mdv_cmd() abort
+  let l:cmd = ['mdv', '--port', string(g:mdv_port)]
+  if !empty(g:mdv_socket)
+    call extend(l:cmd, ['--socket', g:mdv_socket])
+  endif
+  if g:mdv_tls
+    call add(l:cmd, '--tls')
+  endif
+  return l:cmd
+endfunction
+
 " Start mdv server
 function! mdv#start() abort
   if mdv#is_running()
@@ -69,14 +117,14 @@ function! mdv#start() abort
     return 1
   endif
 
-  let l:cmd = ['mdv', '--port', string(g:mdv_port)]
+  let l:cmd = s:mdv_cmd()
 
   if has('nvim')
     let s:mdv_job = jobstart(l:cmd, {'detach': v:true})
   elseif has('job')
     let s:mdv_job = job_start(l:cmd, {'stoponexit': ''})
   else
-    silent execute '!mdv --port ' . g:mdv_port . ' &'
+    silent execute '!' . join(map(copy(l:cmd), 'shellescape(v:val)'), ' ') . ' &'
   endif
 
   " Wait for server
@@ -97,7 +145,11 @@ endfunction
 
 " Stop mdv server
 function! mdv#stop() abort
-  if has('nvim') && s:mdv_job != v:null
+  if executable('mdv')
+    " Shuts down gracefully through the authenticated API
+    call system(join(map(s:mdv_cmd() + ['stop'], 'shellescape(v:val)'), ' '))
+    let s:mdv_job = v:null
+  elseif has('nvim') && s:mdv_job != v:null
     call jobstop(s:mdv_job)
     let s:mdv_job = v:null
   elseif has('job') && s:mdv_job != v:null
@@ -143,8 +195,8 @@ function! s:refresh_workspace_cache() abort
     return
   endif
 
-  let l:url = s:base_url() . '/api/status'
-  let l:result = system('curl -s --max-time 1 ' . shellescape(l:url))
+  let l:url = s:api_url() . '/api/status'
+  let l:result = system(s:curl_cmd() . '--max-time 1 ' . shellescape(l:url))
 
   try
     let l:resp = json_decode(l:result)
@@ -164,12 +216,22 @@ function! s:find_workspace_for_file(path) abort
   return {}
 endfunction
 
+" Whether path has one of g:mdv_extensions or is named one of
+" g:mdv_filenames, ignoring case like the server does.
+function! mdv#is_markdown(path) abort
+  let l:ext = fnamemodify(a:path, ':e')
+  if empty(l:ext)
+    return index(get(g:, 'mdv_filenames', ['README']), fnamemodify(a:path, ':t'), 0, 1) >= 0
+  endif
+  return index(get(g:, 'mdv_extensions', ['md']), l:ext, 0, 1) >= 0
+endfunction
+
 " Check if current buffer is a valid markdown file in a registered workspace.
 " Returns workspace info dict if valid, empty dict otherwise.
 " Refreshes cache if empty.
 function! s:get_current_md_workspace() abort
   let l:path = expand('%:p')
-  if l:path !~# '\.md$'
+  if !mdv#is_markdown(l:path)
     return {}
   endif
 
@@ -187,7 +249,7 @@ endfunction
 " Execute curl GET request and return parsed JSON response.
 " Returns empty dict on error.
 function! s:curl_get(url) abort
-  let l:result = system('curl -s --max-time 1 ' . shellescape(a:url))
+  let l:result = system(s:curl_cmd() . '--max-time 1 ' . shellescape(a:url))
   try
     return json_decode(l:result)
   catch
@@ -198,7 +260,7 @@ endfunction
 " Execute curl POST request with JSON body.
 " Returns parsed JSON response or empty dict on error.
 function! s:curl_post(url, json_body) abort
-  let l:result = system('curl -s -X POST -H "Content-Type: application/json" -d ' . shellescape(a:json_body) . ' ' . shellescape(a:url))
+  let l:result = system(s:curl_cmd() . '-X POST -H "Content-Type: application/json" -d ' . shellescape(a:json_body) . ' ' . shellescape(a:url))
   try
     return json_decode(l:result)
   catch
@@ -209,7 +271,7 @@ endfunction
 " Execute curl DELETE request.
 " Returns parsed JSON response or empty dict on error.
 function! s:curl_delete(url) abort
-  let l:result = system('curl -s -X DELETE ' . shellescape(a:url))
+  let l:result = system(s:curl_cmd() . '-X DELETE ' . shellescape(a:url))
   try
     return json_decode(l:result)
   catch
@@ -229,7 +291,7 @@ function! mdv#register_workspace(...) abort
     return s:registered_workspaces[l:root]
   endif
 
-  let l:url = s:base_url() . '/api/workspace/register'
+  let l:url = s:api_url() . '/api/workspace/register'
   let l:json = '{"path":"' . escape(l:root, '"') . '"}'
   let l:resp = s:curl_post(l:url, l:json)
 
@@ -253,7 +315,7 @@ function! mdv#active() abort
     return
   endif
 
-  let l:url = s:base_url() . '/api/active?path=' . expand('%:p')
+  let l:url = s:api_url() . '/api/active?path=' . expand('%:p')
   let l:resp = s:curl_get(l:url)
 
   " Clear cache on error (workspace may have been removed externally)
@@ -269,7 +331,7 @@ function! mdv#open() abort
   endif
 
   let l:path = expand('%:p')
-  if l:path !~# '\.md$'
+  if !mdv#is_markdown(l:path)
     echoerr 'Current file is not a markdown file'
     return
   endif
@@ -324,8 +386,8 @@ function! mdv#sync_scroll() abort
   let l:current = line('.')
   let l:percent = ((l:current - 1) * 100) / (l:total - 1)
 
-  let l:url = s:base_url() . '/api/remote/scroll?percent=' . l:percent
-  call s:run_silent(['curl', '-s', l:url])
+  let l:url = s:api_url() . '/api/remote/scroll?percent=' . l:percent
+  call s:run_silent(s:curl_list() + [l:url])
 endfunction
 
 " Toggle scroll sync
@@ -341,8 +403,11 @@ function! mdv#status() abort
     return
   endif
 
-  let l:resp = s:curl_get(s:base_url() . '/api/status')
+  let l:resp = s:curl_get(s:api_url() . '/api/status')
   echo 'mdv server running at ' . s:base_url()
+  if !empty(g:mdv_socket)
+    echo 'API socket: ' . g:mdv_socket
+  endif
   echo 'Scroll sync: ' . (g:mdv_sync_scroll ? 'ON' : 'OFF')
 
   if has_key(l:resp, 'workspaces')
@@ -371,7 +436,7 @@ function! mdv#workspace_add(...) abort
     let l:path = l:path[:-2]
   endif
 
-  let l:url = s:base_url() . '/api/workspace/register'
+  let l:url = s:api_url() . '/api/workspace/register'
   let l:json = '{"path":"' . escape(l:path, '"') . '"}'
   let l:resp = s:curl_post(l:url, l:json)
 
@@ -425,7 +490,7 @@ function! mdv#workspace_remove(...) abort
     return
   endif
 
-  let l:resp = s:curl_get(s:base_url() . '/api/status')
+  let l:resp = s:curl_get(s:api_url() . '/api/status')
   if empty(l:resp) || empty(get(l:resp, 'workspaces', []))
     echo 'No workspaces registered'
     return
@@ -436,7 +501,7 @@ function! mdv#workspace_remove(...) abort
     return
   endif
 
-  let l:del_resp = s:curl_delete(s:base_url() . '/api/workspace/' . l:workspace_id)
+  let l:del_resp = s:curl_delete(s:api_url() . '/api/workspace/' . l:workspace_id)
 
   if empty(l:del_resp)
     echoerr 'Failed to remove workspace'
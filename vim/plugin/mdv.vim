@@ -15,11 +15,30 @@ command! MdvScrollToggle call mdv#toggle_scroll()
 command! -nargs=? -complete=dir MdvWorkspaceAdd call mdv#workspace_add(<f-args>)
 command! -nargs=? MdvWorkspaceRemove call mdv#workspace_remove(<f-args>)
 
+" File extensions and extensionless file names treated as markdown,
+" matched case-insensitively (keep in sync with the server's [files])
+if !exists('g:mdv_extensions')
+  let g:mdv_extensions = ['md', 'markdown', 'mdown', 'mkd', 'mdx']
+endif
+
+if !exists('g:mdv_filenames')
+  let g:mdv_filenames = ['README']
+endif
+
+" Case-insensitive file pattern: 'md' -> '[mM][dD]'
+function! s:ignore_case(name) abort
+  return substitute(a:name, '\a', '\="[" . tolower(submatch(0)) . toupper(submatch(0)) . "]"', 'g')
+endfunction
+
+let s:patterns = map(copy(g:mdv_extensions), '"*." . s:ignore_case(v:val)')
+      \ + map(copy(g:mdv_filenames), 's:ignore_case(v:val)')
+let s:pattern = join(s:patterns, ',')
+
 " Autocommands for markdown files
 augroup mdv_sync
   autocmd!
   " Auto-register workspace and navigate on BufEnter
-  autocmd BufEnter *.md call mdv#on_buf_enter()
+  execute 'autocmd BufEnter ' . s:pattern . ' call mdv#on_buf_enter()'
   " Sync scroll on cursor movement
-  autocmd CursorMoved,CursorMovedI *.md call mdv#sync_scroll()
+  execute 'autocmd CursorMoved,CursorMovedI ' . s:pattern . ' call mdv#sync_scroll()'
 augroup END